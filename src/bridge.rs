@@ -0,0 +1,162 @@
+//! Bridge/config export: encodes the set of transports HAM found working
+//! into a compact, self-describing URI (mirroring the `sdns://` stamp
+//! approach) and renders it as a scannable terminal QR code, so a peer in
+//! the same filtered region can reuse a known-good path without re-running
+//! the full probe battery themselves.
+
+use base64::Engine;
+use colored::*;
+use qrcode::render::unicode;
+use qrcode::QrCode;
+use serde::{Deserialize, Serialize};
+
+const SCHEME: &str = "ham";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingTransport {
+    /// e.g. "tcp", "udp", "quic", "doh", "dot"
+    pub transport: String,
+    pub endpoint: String,
+    pub port: u16,
+    /// Free-form flags, e.g. "fragmented" for a TLS path that only works
+    /// with ClientHello fragmentation.
+    pub flags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BridgeConfig {
+    pub version: u8,
+    pub transports: Vec<WorkingTransport>,
+    /// Resolver stamps (see [`crate::dns_stamp`]) found to be reachable.
+    pub resolver_stamps: Vec<String>,
+}
+
+impl BridgeConfig {
+    pub fn new(transports: Vec<WorkingTransport>, resolver_stamps: Vec<String>) -> Self {
+        Self { version: VERSION, transports, resolver_stamps }
+    }
+
+    /// Encodes this config as a versioned base64 payload behind a `ham://`
+    /// URI, in the same spirit as an `sdns://` stamp.
+    pub fn to_uri(&self) -> Result<String, serde_json::Error> {
+        let json = serde_json::to_vec(self)?;
+        let encoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json);
+        Ok(format!("{SCHEME}://{encoded}"))
+    }
+
+    pub fn from_uri(uri: &str) -> Result<Self, String> {
+        let encoded = uri
+            .strip_prefix(&format!("{SCHEME}://"))
+            .ok_or_else(|| format!("missing {SCHEME}:// scheme"))?;
+        let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|e| e.to_string())?;
+        serde_json::from_slice(&bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// Builds a [`BridgeConfig`] by probing TCP, HTTPS, fragmented TLS, QUIC and
+/// a DoH resolver stamp, keeping only the ones that actually came back
+/// reachable.
+pub async fn discover_working_transports() -> BridgeConfig {
+    let mut transports = Vec::new();
+
+    if test_tcp_connection_reachable("8.8.8.8:53").await {
+        transports.push(WorkingTransport {
+            transport: "tcp".to_string(),
+            endpoint: "8.8.8.8".to_string(),
+            port: 53,
+            flags: vec![],
+        });
+    }
+
+    let https_score = test_https_reachable().await;
+    if https_score {
+        transports.push(WorkingTransport {
+            transport: "tls".to_string(),
+            endpoint: "www.google.com".to_string(),
+            port: 443,
+            flags: vec![],
+        });
+    }
+
+    let fragmentation = crate::tls_frag::probe_fragmentation(crate::tls_frag::DEFAULT_FILTERED_SNI).await;
+    if fragmentation.fragmentation_bypasses_dpi() {
+        transports.push(WorkingTransport {
+            transport: "tls".to_string(),
+            endpoint: crate::tls_frag::DEFAULT_FILTERED_SNI.to_string(),
+            port: 443,
+            flags: vec!["fragmented".to_string()],
+        });
+    }
+
+    let quic_result = crate::quic::probe_quic("www.google.com", 443).await;
+    if quic_result.outcome == crate::quic::QuicProbeOutcome::HandshakeCompleted {
+        transports.push(WorkingTransport {
+            transport: "quic".to_string(),
+            endpoint: quic_result.host,
+            port: quic_result.port,
+            flags: vec![],
+        });
+    }
+
+    // probe_resolvers only reports back a provider_name/score/detail per
+    // stamp, not the stamp itself, so zip its results against the input
+    // list (preserved in order) to recover the actual sdns:// URI a peer
+    // could reuse - a human-readable provider name alone isn't something
+    // anyone can resolve through.
+    let candidate_stamps = ["sdns://AgcAAAAAAAAABzEuMS4xLjEAC2Nsb3VkZmxhcmUtZG5zLmNvbQovZG5zLXF1ZXJ5"];
+    let resolver_stamps = crate::edns::probe_resolvers(&candidate_stamps)
+        .await
+        .into_iter()
+        .zip(candidate_stamps.iter())
+        .filter(|(r, _)| r.score >= 7)
+        .map(|(_, stamp)| stamp.to_string())
+        .collect();
+
+    BridgeConfig::new(transports, resolver_stamps)
+}
+
+async fn test_tcp_connection_reachable(addr: &str) -> bool {
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+    matches!(timeout(Duration::from_secs(3), TcpStream::connect(addr)).await, Ok(Ok(_)))
+}
+
+async fn test_https_reachable() -> bool {
+    use std::time::Duration;
+    use tokio::time::timeout;
+    matches!(
+        timeout(Duration::from_secs(5), reqwest::get("https://www.google.com")).await,
+        Ok(Ok(resp)) if resp.status().is_success()
+    )
+}
+
+/// Renders `config` as a scannable terminal QR code using unicode
+/// half-blocks, after printing the underlying URI for copy/paste use.
+pub fn print_qr(config: &BridgeConfig) {
+    let uri = match config.to_uri() {
+        Ok(uri) => uri,
+        Err(e) => {
+            println!("{}", format!("failed to encode bridge config: {e}").red());
+            return;
+        }
+    };
+
+    println!("{}", "Bridge config URI:".yellow());
+    println!("{uri}\n");
+
+    match QrCode::new(uri.as_bytes()) {
+        Ok(code) => {
+            let rendered = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark)
+                .build();
+            println!("{rendered}");
+        }
+        Err(e) => println!("{}", format!("failed to render QR code: {e}").red()),
+    }
+}
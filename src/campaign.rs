@@ -0,0 +1,259 @@
+//! YAML-driven measurement-plan runner.
+//!
+//! The `test_*` probes are individual async functions with no way to
+//! sequence, repeat, or aggregate them into a repeatable campaign. This
+//! module loads a YAML plan describing a list of steps - each naming a
+//! probe, its target, an iteration count, a concurrency limit, and optional
+//! score-range assertions - executes them, and captures per-step and
+//! aggregate results into a [`CampaignReport`]. A later step can reference
+//! an earlier step's captured output via `{{step_name.score}}`
+//! interpolation, and `with_items` fans a single step out across a list of
+//! targets.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CampaignPlan {
+    pub name: String,
+    pub steps: Vec<StepSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StepSpec {
+    pub name: String,
+    /// Name of a registered probe - see [`probe_registry`].
+    pub probe: String,
+    /// Target to pass to the probe; may reference an earlier step's output
+    /// via `{{step_name.score}}`.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// Run the same probe against each of these targets instead of `target`.
+    #[serde(default)]
+    pub with_items: Option<Vec<String>>,
+    #[serde(default = "default_iterations")]
+    pub iterations: u32,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: u32,
+    #[serde(default)]
+    pub assert_score_min: Option<u8>,
+    #[serde(default)]
+    pub assert_score_max: Option<u8>,
+}
+
+fn default_iterations() -> u32 {
+    1
+}
+
+fn default_concurrency() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunResult {
+    pub target: String,
+    pub score: u8,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepReport {
+    pub name: String,
+    pub probe: String,
+    pub runs: Vec<RunResult>,
+    pub average_score: f32,
+    /// `None` when the step had no assertions; `Some(true)` when every run
+    /// satisfied `assert_score_min`/`assert_score_max`. Always `Some(false)`
+    /// when the step names a probe that isn't registered.
+    pub assertion_passed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CampaignReport {
+    pub name: String,
+    pub steps: Vec<StepReport>,
+}
+
+impl CampaignReport {
+    pub fn all_assertions_passed(&self) -> bool {
+        self.steps.iter().all(|s| s.assertion_passed.unwrap_or(true))
+    }
+}
+
+type ProbeFn = fn(String) -> Pin<Box<dyn Future<Output = u8> + Send>>;
+
+/// Maps a probe name used in a YAML plan to the HAM test function it drives.
+fn probe_registry() -> HashMap<&'static str, ProbeFn> {
+    let mut registry: HashMap<&'static str, ProbeFn> = HashMap::new();
+    registry.insert("tcp", |target| {
+        Box::pin(async move { crate::test_tcp_connection(&target, Duration::from_secs(3)).await })
+    });
+    registry.insert("https", |_target| Box::pin(crate::test_https_connection()));
+    registry.insert("dns", |_target| Box::pin(crate::test_dns_resolution()));
+    registry.insert("udp", |_target| Box::pin(crate::test_udp()));
+    registry.insert("quic", |target| {
+        Box::pin(async move {
+            let (host, port) = target.split_once(':').unwrap_or((target.as_str(), "443"));
+            let port: u16 = port.parse().unwrap_or(443);
+            crate::quic::test_quic_connectivity(host, port).await
+        })
+    });
+    registry.insert("tls_fragmentation", |target| {
+        Box::pin(async move { crate::tls_frag::probe_fragmentation(&target).await.score() })
+    });
+    registry
+}
+
+/// Loads a [`CampaignPlan`] from a YAML file at `path`.
+pub async fn load_plan(path: &str) -> Result<CampaignPlan, String> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("could not read campaign file {path}: {e}"))?;
+    serde_yaml::from_str(&content).map_err(|e| format!("invalid campaign YAML: {e}"))
+}
+
+/// Executes every step of `plan` in order, capturing each step's output so
+/// later steps can interpolate it, and returns the aggregate report.
+pub async fn run_plan(plan: &CampaignPlan) -> CampaignReport {
+    let registry = probe_registry();
+    let mut captures: HashMap<String, u8> = HashMap::new();
+    let mut step_reports = Vec::with_capacity(plan.steps.len());
+
+    for step in &plan.steps {
+        let report = run_step(step, &registry, &captures).await;
+        if let Some(last) = report.runs.last() {
+            captures.insert(step.name.clone(), last.score);
+        }
+        step_reports.push(report);
+    }
+
+    CampaignReport { name: plan.name.clone(), steps: step_reports }
+}
+
+async fn run_step(step: &StepSpec, registry: &HashMap<&'static str, ProbeFn>, captures: &HashMap<String, u8>) -> StepReport {
+    let Some(probe_fn) = registry.get(step.probe.as_str()).copied() else {
+        return StepReport {
+            name: step.name.clone(),
+            probe: step.probe.clone(),
+            runs: vec![],
+            average_score: 0.0,
+            assertion_passed: Some(false),
+        };
+    };
+
+    let targets = expand_targets(step, captures);
+    let concurrency = step.concurrency.max(1) as usize;
+
+    let mut work: Vec<(String, u32)> = Vec::new();
+    for target in &targets {
+        for _ in 0..step.iterations.max(1) {
+            work.push((target.clone(), 0));
+        }
+    }
+
+    let mut runs = Vec::with_capacity(work.len());
+    let mut in_flight = FuturesUnordered::new();
+    let mut queue = work.into_iter();
+
+    for _ in 0..concurrency {
+        if let Some((target, _)) = queue.next() {
+            in_flight.push(run_one(probe_fn, target));
+        }
+    }
+
+    while let Some(result) = in_flight.next().await {
+        runs.push(result);
+        if let Some((target, _)) = queue.next() {
+            in_flight.push(run_one(probe_fn, target));
+        }
+    }
+
+    let average_score = if runs.is_empty() {
+        0.0
+    } else {
+        runs.iter().map(|r| r.score as f32).sum::<f32>() / runs.len() as f32
+    };
+
+    let assertion_passed = if step.assert_score_min.is_some() || step.assert_score_max.is_some() {
+        Some(runs.iter().all(|r| {
+            step.assert_score_min.map_or(true, |min| r.score >= min)
+                && step.assert_score_max.map_or(true, |max| r.score <= max)
+        }))
+    } else {
+        None
+    };
+
+    StepReport {
+        name: step.name.clone(),
+        probe: step.probe.clone(),
+        runs,
+        average_score,
+        assertion_passed,
+    }
+}
+
+async fn run_one(probe_fn: ProbeFn, target: String) -> RunResult {
+    let score = probe_fn(target.clone()).await;
+    RunResult { target, score }
+}
+
+/// Resolves `with_items`/`target` into the concrete list of targets to run
+/// the probe against, interpolating any `{{step_name.score}}` references
+/// against already-captured step outputs.
+fn expand_targets(step: &StepSpec, captures: &HashMap<String, u8>) -> Vec<String> {
+    let raw_targets = match &step.with_items {
+        Some(items) => items.clone(),
+        None => vec![step.target.clone().unwrap_or_default()],
+    };
+
+    raw_targets.into_iter().map(|t| interpolate(&t, captures)).collect()
+}
+
+fn interpolate(input: &str, captures: &HashMap<String, u8>) -> String {
+    let mut output = input.to_string();
+    for (name, score) in captures {
+        let needle = format!("{{{{{name}.score}}}}");
+        output = output.replace(&needle, &score.to_string());
+    }
+    output
+}
+
+/// Runs the campaign at `path` and prints a report in the same style as the
+/// other `run_*` commands.
+pub async fn run_campaign(path: &str) {
+    use colored::*;
+
+    let plan = match load_plan(path).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!("{}", e.red());
+            return;
+        }
+    };
+
+    println!("{}", format!("HAM Campaign: {}", plan.name).cyan().bold());
+    let report = run_plan(&plan).await;
+
+    for step in &report.steps {
+        println!("\n   {} ({})", step.name.yellow(), step.probe);
+        for run in &step.runs {
+            println!("      {} -> {}/10", run.target, run.score);
+        }
+        println!("      average: {:.1}/10", step.average_score);
+        match step.assertion_passed {
+            Some(true) => println!("      {}", "assertions passed".green()),
+            Some(false) => println!("      {}", "assertions FAILED".red()),
+            None => {}
+        }
+    }
+
+    if report.all_assertions_passed() {
+        println!("\n{}", "All step assertions passed.".green().bold());
+    } else {
+        println!("\n{}", "One or more step assertions failed.".red().bold());
+    }
+}
@@ -0,0 +1,272 @@
+//! Detection of DNS injection/poisoning, as distinct from plain blocking.
+//!
+//! `analyze_censorship` only checks whether a domain resolves at all, which
+//! misses the GFW/Iran tactic of answering with a *forged* record instead of
+//! dropping the query. We send a raw UDP DNS query ourselves (bypassing the
+//! OS stub resolver and its single-answer semantics) and keep the socket
+//! open past the first reply, collecting everything that arrives within the
+//! collection window. On-path injectors race the legitimate resolver and
+//! frequently lose, so a second, earlier, or otherwise-impossible answer is
+//! strong evidence of tampering.
+
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use hickory_proto::op::{Message, MessageType, Query, ResponseCode};
+use hickory_proto::rr::{Name, RData, RecordType};
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+const COLLECTION_WINDOW: Duration = Duration::from_millis(1000);
+
+/// A known-blocked domain, probed against a real resolver.
+const DEFAULT_PROBE_DOMAINS: &[&str] = &["twitter.com", "facebook.com", "youtube.com"];
+
+/// An address that answers no DNS queries at all; a reply arriving from our
+/// query to *this* address can only mean an on-path device is injecting
+/// responses rather than forwarding to a real resolver.
+const NON_RESOLVER_PROBE: &str = "192.0.2.1:53"; // TEST-NET-1, RFC 5737
+
+/// A small embedded list of IPs that censors commonly inject in place of the
+/// real answer (sinkholes/blockpages/bogons). Not exhaustive - just enough
+/// to corroborate a multi-answer or too-fast verdict.
+const KNOWN_SINKHOLES: &[&str] = &[
+    "0.0.0.0",
+    "127.0.0.1",
+    "10.10.34.34",   // commonly observed GFW injected address
+    "243.185.187.39", // commonly observed GFW injected address
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InjectionVerdict {
+    Clean,
+    InjectedIp,
+    /// A resolver actually replied with RCODE=NXDOMAIN for a domain that
+    /// genuinely exists - distinct from [`InjectionVerdict::Timeout`],
+    /// where nothing came back at all.
+    NxDomainForged,
+    /// No response packet arrived within the collection window at all.
+    /// This alone isn't evidence of forgery - a dropped query looks
+    /// identical to an overloaded or unreachable resolver - so it's kept
+    /// separate from a genuine, parsed NXDOMAIN reply.
+    Timeout,
+}
+
+impl std::fmt::Display for InjectionVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InjectionVerdict::Clean => write!(f, "clean"),
+            InjectionVerdict::InjectedIp => write!(f, "injected-IP"),
+            InjectionVerdict::NxDomainForged => write!(f, "NXDOMAIN-forged"),
+            InjectionVerdict::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InjectionReport {
+    pub domain: String,
+    pub verdict: InjectionVerdict,
+    pub answers: Vec<IpAddr>,
+    pub detail: String,
+}
+
+/// Checks a single domain for DNS injection by racing a real resolver query
+/// against a deliberately-unreachable "resolver" and collecting every answer
+/// that shows up within [`COLLECTION_WINDOW`].
+pub async fn check_domain(domain: &str, resolver: SocketAddr) -> InjectionReport {
+    let real_responses = match collect_answers(domain, resolver).await {
+        Ok(responses) => responses,
+        Err(e) => {
+            return InjectionReport {
+                domain: domain.to_string(),
+                verdict: InjectionVerdict::Clean,
+                answers: vec![],
+                detail: format!("query failed: {e}"),
+            }
+        }
+    };
+
+    let bogus_answers = collect_answers(domain, NON_RESOLVER_PROBE.parse().unwrap())
+        .await
+        .map(|r| r.answers)
+        .unwrap_or_default();
+
+    if !bogus_answers.is_empty() {
+        let ips: Vec<IpAddr> = bogus_answers.iter().map(|a| a.ip).collect();
+        return InjectionReport {
+            domain: domain.to_string(),
+            verdict: InjectionVerdict::InjectedIp,
+            answers: ips,
+            detail: format!(
+                "received {} answer(s) from a non-resolver IP ({}) - proof of on-path injection",
+                bogus_answers.len(),
+                NON_RESOLVER_PROBE
+            ),
+        };
+    }
+
+    evaluate(domain, real_responses)
+}
+
+fn evaluate(domain: &str, responses: CollectedResponses) -> InjectionReport {
+    if !responses.saw_any_response {
+        return InjectionReport {
+            domain: domain.to_string(),
+            verdict: InjectionVerdict::Timeout,
+            answers: vec![],
+            detail: "no response at all arrived within the collection window".to_string(),
+        };
+    }
+
+    let answers = responses.answers;
+    if answers.is_empty() {
+        return if responses.saw_nxdomain {
+            InjectionReport {
+                domain: domain.to_string(),
+                verdict: InjectionVerdict::NxDomainForged,
+                answers: vec![],
+                detail: "resolver returned a genuine RCODE=NXDOMAIN for a domain known to exist".to_string(),
+            }
+        } else {
+            InjectionReport {
+                domain: domain.to_string(),
+                verdict: InjectionVerdict::Clean,
+                answers: vec![],
+                detail: "resolver replied but with no A records (not NXDOMAIN)".to_string(),
+            }
+        };
+    }
+
+    let distinct_ips: HashSet<IpAddr> = answers.iter().map(|a| a.ip).collect();
+    let earliest = answers.iter().map(|a| a.elapsed).min().unwrap_or_default();
+    let sinkhole_hit = answers.iter().any(|a| is_known_sinkhole(&a.ip));
+    // All A records of one response share its arrival time, so distinct
+    // arrival times count distinct responses.
+    let response_count = answers.iter().map(|a| a.elapsed).collect::<HashSet<Duration>>().len();
+
+    // A round trip to a real authoritative/recursive resolver rarely
+    // completes in under ~5ms even on a LAN; an answer that fast alongside a
+    // legitimate one is a strong injection signal.
+    let suspiciously_fast = earliest < Duration::from_millis(5) && response_count > 1;
+
+    if (response_count > 1 && distinct_ips.len() > 1) || sinkhole_hit || suspiciously_fast {
+        InjectionReport {
+            domain: domain.to_string(),
+            verdict: InjectionVerdict::InjectedIp,
+            answers: distinct_ips.into_iter().collect(),
+            detail: format!(
+                "{} A record(s), first after {:?}{}",
+                answers.len(),
+                earliest,
+                if sinkhole_hit { "; matched known sinkhole list" } else { "" }
+            ),
+        }
+    } else {
+        InjectionReport {
+            domain: domain.to_string(),
+            verdict: InjectionVerdict::Clean,
+            answers: distinct_ips.into_iter().collect(),
+            detail: format!("single consistent answer after {earliest:?}"),
+        }
+    }
+}
+
+fn is_known_sinkhole(ip: &IpAddr) -> bool {
+    KNOWN_SINKHOLES
+        .iter()
+        .any(|s| s.parse::<IpAddr>().as_ref() == Ok(ip))
+}
+
+struct TimedAnswer {
+    ip: IpAddr,
+    elapsed: Duration,
+}
+
+/// What came back (or didn't) from a [`collect_answers`] window, with enough
+/// detail for [`evaluate`] to tell a genuine forged NXDOMAIN reply apart from
+/// plain silence.
+#[derive(Default)]
+struct CollectedResponses {
+    answers: Vec<TimedAnswer>,
+    /// Whether at least one parseable DNS response arrived, regardless of
+    /// whether it carried any A records.
+    saw_any_response: bool,
+    /// Whether any parsed response actually carried RCODE=NXDOMAIN.
+    saw_nxdomain: bool,
+}
+
+/// Sends a single raw UDP `A` query for `domain` to `resolver` and keeps
+/// listening on the same socket for `COLLECTION_WINDOW`, recording every
+/// answer that arrives rather than stopping at the first one.
+async fn collect_answers(domain: &str, resolver: SocketAddr) -> Result<CollectedResponses, String> {
+    let name = Name::from_ascii(format!("{domain}.")).map_err(|e| e.to_string())?;
+
+    let mut query = Message::new();
+    query.set_id(rand_txn_id());
+    query.set_message_type(MessageType::Query);
+    query.set_recursion_desired(true);
+    query.add_query(Query::query(name, RecordType::A));
+
+    let bind_addr = if resolver.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(bind_addr).await.map_err(|e| e.to_string())?;
+    socket.connect(resolver).await.map_err(|e| e.to_string())?;
+
+    let wire = query.to_vec().map_err(|e| e.to_string())?;
+    socket.send(&wire).await.map_err(|e| e.to_string())?;
+
+    let started = Instant::now();
+    let mut collected = CollectedResponses::default();
+    let mut buf = [0u8; 512];
+
+    loop {
+        let remaining = COLLECTION_WINDOW.saturating_sub(started.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        match timeout(remaining, socket.recv(&mut buf)).await {
+            Ok(Ok(len)) => {
+                let elapsed = started.elapsed();
+                if let Ok(response) = Message::from_vec(&buf[..len]) {
+                    collected.saw_any_response = true;
+                    if response.response_code() == ResponseCode::NXDomain {
+                        collected.saw_nxdomain = true;
+                    }
+                    for record in response.answers() {
+                        if let Some(RData::A(addr)) = record.data() {
+                            collected.answers.push(TimedAnswer {
+                                ip: IpAddr::V4((*addr).into()),
+                                elapsed,
+                            });
+                        }
+                    }
+                }
+            }
+            Ok(Err(_)) | Err(_) => break,
+        }
+    }
+
+    Ok(collected)
+}
+
+fn rand_txn_id() -> u16 {
+    // A fixed-seed-free source of entropy without pulling in `rand` just for
+    // a transaction id: the low bits of the current time are adequate here
+    // since we only need to distinguish our own in-flight queries.
+    (std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos()) as u16
+}
+
+/// Runs the injection check against the default domain list and a real
+/// resolver (Google DNS), returning one [`InjectionReport`] per domain.
+pub async fn run_injection_checks() -> Vec<InjectionReport> {
+    let resolver: SocketAddr = "8.8.8.8:53".parse().unwrap();
+    let mut reports = Vec::with_capacity(DEFAULT_PROBE_DOMAINS.len());
+    for domain in DEFAULT_PROBE_DOMAINS {
+        reports.push(check_domain(domain, resolver).await);
+    }
+    reports
+}
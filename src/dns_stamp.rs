@@ -0,0 +1,164 @@
+//! Parsing of DNSCrypt-style `sdns://` stamps.
+//!
+//! A stamp is `sdns://` followed by URL-safe, unpadded base64 of a small
+//! binary structure: a protocol byte, then protocol-specific fields such as
+//! the resolver address, an optional public key, and the provider name. We
+//! only need enough of the format to drive our own probes, not the full
+//! DNSCrypt stamp spec.
+
+use base64::Engine;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StampProtocol {
+    Plain,
+    DnsCrypt,
+    DoH,
+    DoT,
+}
+
+#[derive(Debug, Clone)]
+pub struct DnsStamp {
+    pub protocol: StampProtocol,
+    /// `host:port` (or just `host` when the protocol implies the port).
+    pub address: String,
+    /// DNSCrypt provider public key, hex-encoded, when present.
+    pub public_key: Option<String>,
+    /// Provider name / hostname used for DoH/DoT and as the DNSCrypt
+    /// provider identifier.
+    pub provider_name: String,
+    /// DoH request path, defaulting to `/dns-query`.
+    pub path: String,
+}
+
+#[derive(Debug)]
+pub enum StampError {
+    MissingScheme,
+    InvalidBase64,
+    Truncated,
+    UnknownProtocol(u8),
+}
+
+impl std::fmt::Display for StampError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StampError::MissingScheme => write!(f, "stamp is missing the sdns:// scheme"),
+            StampError::InvalidBase64 => write!(f, "stamp is not valid base64"),
+            StampError::Truncated => write!(f, "stamp is shorter than its protocol requires"),
+            StampError::UnknownProtocol(b) => write!(f, "unknown stamp protocol byte 0x{b:02x}"),
+        }
+    }
+}
+
+impl std::error::Error for StampError {}
+
+/// Parses an `sdns://` stamp into its components.
+pub fn parse_stamp(stamp: &str) -> Result<DnsStamp, StampError> {
+    let encoded = stamp.strip_prefix("sdns://").ok_or(StampError::MissingScheme)?;
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_| StampError::InvalidBase64)?;
+
+    let mut cursor = Cursor::new(&bytes);
+    let protocol_byte = cursor.take_u8()?;
+    let protocol = match protocol_byte {
+        0x00 => StampProtocol::Plain,
+        0x01 => StampProtocol::DnsCrypt,
+        0x02 => StampProtocol::DoH,
+        0x03 => StampProtocol::DoT,
+        other => return Err(StampError::UnknownProtocol(other)),
+    };
+
+    // All of the protocols we care about share the same leading layout:
+    // props (8 bytes, ignored - feature flags we don't act on), then a
+    // length-prefixed address, and for DNSCrypt a length-prefixed public key.
+    cursor.skip(8)?;
+    let address = cursor.take_lp_string()?;
+    let public_key = if protocol == StampProtocol::DnsCrypt {
+        Some(hex_encode(&cursor.take_lp_bytes()?))
+    } else {
+        None
+    };
+    // DoH/DoT carry a certificate-hashes array between the address and the
+    // provider name - a sequence of LP byte-strings where the top bit of
+    // each length byte signals "more entries follow". We don't validate
+    // pinned hashes, but we still have to skip over them to reach the
+    // provider name.
+    if matches!(protocol, StampProtocol::DoH | StampProtocol::DoT) {
+        cursor.skip_lp_array()?;
+    }
+    let provider_name = cursor.take_lp_string()?;
+    let path = if protocol == StampProtocol::DoH {
+        let p = cursor.take_lp_string().unwrap_or_default();
+        if p.is_empty() { "/dns-query".to_string() } else { p }
+    } else {
+        String::new()
+    };
+
+    Ok(DnsStamp {
+        protocol,
+        address,
+        public_key,
+        provider_name,
+        path,
+    })
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take_u8(&mut self) -> Result<u8, StampError> {
+        let b = *self.bytes.get(self.pos).ok_or(StampError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<(), StampError> {
+        if self.pos + n > self.bytes.len() {
+            return Err(StampError::Truncated);
+        }
+        self.pos += n;
+        Ok(())
+    }
+
+    fn take_lp_bytes(&mut self) -> Result<Vec<u8>, StampError> {
+        let len = self.take_u8()? as usize;
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(StampError::Truncated)?;
+        self.pos += len;
+        Ok(slice.to_vec())
+    }
+
+    fn take_lp_string(&mut self) -> Result<String, StampError> {
+        let bytes = self.take_lp_bytes()?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// Skips a DNSCrypt-stamp-style LP array: a sequence of length-prefixed
+    /// byte strings where bit 0x80 of the length byte means "another entry
+    /// follows". An empty array is encoded as a single zero-length entry
+    /// with that bit clear.
+    fn skip_lp_array(&mut self) -> Result<(), StampError> {
+        loop {
+            let len_byte = self.take_u8()?;
+            let len = (len_byte & 0x7f) as usize;
+            self.skip(len)?;
+            if len_byte & 0x80 == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
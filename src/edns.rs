@@ -0,0 +1,248 @@
+//! Encrypted DNS transport probing (DoH / DoT / DNSCrypt).
+//!
+//! The system resolver path (`dns_lookup::lookup_host`) tells us nothing
+//! about whether *encrypted* DNS still works once plaintext UDP/53 is
+//! poisoned. This module resolves a control domain over each transport
+//! described by a resolver's DNS stamp and scores reachability the same way
+//! the rest of HAM's protocol probes do.
+
+use std::time::Duration;
+
+use colored::*;
+
+use crate::dns_stamp::{parse_stamp, DnsStamp, StampProtocol};
+
+const CONTROL_DOMAIN: &str = "google.com";
+
+#[derive(Debug, Clone)]
+pub struct EncryptedDnsResult {
+    pub provider_name: String,
+    pub protocol: StampProtocol,
+    pub score: u8,
+    pub detail: String,
+}
+
+/// Probes a list of `sdns://` resolver stamps over their declared transport
+/// and returns one result per resolver.
+pub async fn probe_resolvers(stamps: &[&str]) -> Vec<EncryptedDnsResult> {
+    let mut results = Vec::with_capacity(stamps.len());
+    for stamp in stamps {
+        let result = match parse_stamp(stamp) {
+            Ok(parsed) => probe_one(&parsed).await,
+            Err(e) => EncryptedDnsResult {
+                provider_name: stamp.to_string(),
+                protocol: StampProtocol::Plain,
+                score: 0,
+                detail: format!("unparseable stamp: {e}"),
+            },
+        };
+        results.push(result);
+    }
+    results
+}
+
+async fn probe_one(stamp: &DnsStamp) -> EncryptedDnsResult {
+    let (score, detail) = match stamp.protocol {
+        StampProtocol::DoH => probe_doh(stamp).await,
+        StampProtocol::DoT => probe_dot(stamp).await,
+        StampProtocol::DnsCrypt => probe_dnscrypt(stamp).await,
+        StampProtocol::Plain => (0, "plain stamps are not an encrypted transport".to_string()),
+    };
+    EncryptedDnsResult {
+        provider_name: stamp.provider_name.clone(),
+        protocol: stamp.protocol,
+        score,
+        detail,
+    }
+}
+
+/// RFC 8484 DNS-over-HTTPS: POST a minimal DNS query wire-format message to
+/// the resolver's `/dns-query` path and check we get back a well-formed
+/// `application/dns-message` response.
+async fn probe_doh(stamp: &DnsStamp) -> (u8, String) {
+    let url = format!("https://{}{}", stamp.address, stamp.path);
+    let query = build_dns_query(CONTROL_DOMAIN);
+
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(5)).build() {
+        Ok(c) => c,
+        Err(e) => return (0, format!("client build failed: {e}")),
+    };
+
+    let response = client
+        .post(&url)
+        .header("content-type", "application/dns-message")
+        .header("accept", "application/dns-message")
+        .body(query)
+        .send()
+        .await;
+
+    match response {
+        Ok(resp) if resp.status().is_success() => match resp.bytes().await {
+            Ok(body) if body.len() > 12 => (10, format!("DoH resolved via {url}")),
+            _ => (4, "DoH request succeeded but body was too short to be a DNS message".to_string()),
+        },
+        Ok(resp) => (3, format!("DoH endpoint returned HTTP {}", resp.status())),
+        Err(e) if e.is_timeout() => (1, "DoH request timed out".to_string()),
+        Err(e) => (0, format!("DoH request failed: {e}")),
+    }
+}
+
+/// DNS-over-TLS: open a TLS connection to port 853 (or the port embedded in
+/// the stamp's address) and send a length-prefixed DNS query per RFC 7858.
+async fn probe_dot(stamp: &DnsStamp) -> (u8, String) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+    use tokio_rustls::rustls;
+    use tokio_rustls::TlsConnector;
+
+    let addr = if stamp.address.contains(':') {
+        stamp.address.clone()
+    } else {
+        format!("{}:853", stamp.address)
+    };
+    let sni_name = stamp.provider_name.trim_start_matches("2.").to_string();
+    let sni_name = if sni_name.is_empty() { stamp.address.clone() } else { sni_name };
+
+    let tcp = match timeout(Duration::from_secs(5), TcpStream::connect(&addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(e)) => return (0, format!("DoT TCP connect failed: {e}")),
+        Err(_) => return (1, "DoT TCP connect timed out".to_string()),
+    };
+
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        rustls::OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    let config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_store)
+        .with_no_client_auth();
+    let connector = TlsConnector::from(std::sync::Arc::new(config));
+
+    let server_name = match rustls::ServerName::try_from(sni_name.as_str()) {
+        Ok(name) => name,
+        Err(_) => return (0, format!("invalid DoT SNI name: {sni_name}")),
+    };
+
+    let mut tls = match timeout(Duration::from_secs(5), connector.connect(server_name, tcp)).await {
+        Ok(Ok(tls)) => tls,
+        Ok(Err(e)) => return (2, format!("DoT TLS handshake failed: {e}")),
+        Err(_) => return (2, "DoT TLS handshake timed out".to_string()),
+    };
+
+    let query = build_dns_query(CONTROL_DOMAIN);
+    let mut framed = Vec::with_capacity(query.len() + 2);
+    framed.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    framed.extend_from_slice(&query);
+
+    if tls.write_all(&framed).await.is_err() {
+        return (3, "DoT query write failed after handshake".to_string());
+    }
+
+    let mut len_buf = [0u8; 2];
+    match timeout(Duration::from_secs(3), tls.read_exact(&mut len_buf)).await {
+        Ok(Ok(_)) => (10, format!("DoT resolved via {addr}")),
+        Ok(Err(e)) => (4, format!("DoT response read failed: {e}")),
+        Err(_) => (4, "DoT response timed out after a successful handshake".to_string()),
+    }
+}
+
+/// DNSCrypt: we don't implement the full X25519/XSalsa20Poly1305 certificate
+/// exchange here. Instead a plain DNS A query is sent over UDP to the stamp's
+/// address (port 443 unless the address names one), and any reply is taken
+/// as a sign that the path is open; TCP is not tried.
+async fn probe_dnscrypt(stamp: &DnsStamp) -> (u8, String) {
+    use tokio::net::UdpSocket;
+    use tokio::time::timeout;
+
+    let addr = if stamp.address.contains(':') {
+        stamp.address.clone()
+    } else {
+        format!("{}:443", stamp.address)
+    };
+
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(s) => s,
+        Err(e) => return (0, format!("could not open UDP socket: {e}")),
+    };
+    if socket.connect(&addr).await.is_err() {
+        return (0, format!("DNSCrypt resolver {addr} unreachable"));
+    }
+
+    let probe = build_dns_query(CONTROL_DOMAIN);
+    if socket.send(&probe).await.is_err() {
+        return (1, "DNSCrypt probe datagram send failed".to_string());
+    }
+
+    let mut buf = [0u8; 512];
+    match timeout(Duration::from_secs(3), socket.recv(&mut buf)).await {
+        Ok(Ok(_)) => (7, format!("DNSCrypt resolver {addr} responded to UDP probe (full DNSCrypt certificate exchange not implemented)")),
+        Ok(Err(_)) => (2, format!("DNSCrypt resolver {addr} rejected the probe")),
+        Err(_) => (1, format!("DNSCrypt resolver {addr} did not respond")),
+    }
+}
+
+/// Builds a minimal well-formed DNS query (wire format) for `name`, type A.
+fn build_dns_query(name: &str) -> Vec<u8> {
+    let mut msg = Vec::new();
+    msg.extend_from_slice(&[0x12, 0x34]); // transaction id
+    msg.extend_from_slice(&[0x01, 0x00]); // flags: standard query, recursion desired
+    msg.extend_from_slice(&[0x00, 0x01]); // qdcount
+    msg.extend_from_slice(&[0x00, 0x00]); // ancount
+    msg.extend_from_slice(&[0x00, 0x00]); // nscount
+    msg.extend_from_slice(&[0x00, 0x00]); // arcount
+
+    for label in name.split('.') {
+        msg.push(label.len() as u8);
+        msg.extend_from_slice(label.as_bytes());
+    }
+    msg.push(0); // root label
+
+    msg.extend_from_slice(&[0x00, 0x01]); // qtype A
+    msg.extend_from_slice(&[0x00, 0x01]); // qclass IN
+    msg
+}
+
+/// Runs the encrypted-DNS probe battery against a default set of
+/// well-known public resolver stamps and prints a report in the same style
+/// as the other `analyze_*`/`test_*` commands.
+pub async fn run_test_dns(stamps: Option<Vec<String>>) {
+    println!("{}", "HAM Encrypted DNS Probe".cyan().bold());
+
+    let default_stamps = default_resolver_stamps();
+    let stamp_refs: Vec<&str> = match &stamps {
+        Some(custom) => custom.iter().map(|s| s.as_str()).collect(),
+        None => default_stamps.to_vec(),
+    };
+
+    println!("Probing {} resolver stamp(s)...\n", stamp_refs.len());
+    let results = probe_resolvers(&stamp_refs).await;
+
+    for result in &results {
+        let label = format!("{:?}:{}", result.protocol, result.provider_name);
+        let line = format!("[{label:30}] {}/10 - {}", result.score, result.detail);
+        if result.score >= 7 {
+            println!("   ✓ {}", line.green());
+        } else if result.score >= 4 {
+            println!("   ⚠ {}", line.yellow());
+        } else {
+            println!("   ✗ {}", line.red());
+        }
+    }
+}
+
+/// A handful of well-known public resolvers, expressed as DNS stamps, used
+/// when the user doesn't supply their own list.
+fn default_resolver_stamps() -> [&'static str; 2] {
+    [
+        // Cloudflare DoH (1.1.1.1): props/address/empty-hashes/hostname/path.
+        "sdns://AgAAAAAAAAAABzEuMS4xLjEAEmNsb3VkZmxhcmUtZG5zLmNvbQovZG5zLXF1ZXJ5",
+        // Cloudflare DoT (1.1.1.1): props/address/empty-hashes/hostname.
+        "sdns://AwAAAAAAAAAABzEuMS4xLjEAEmNsb3VkZmxhcmUtZG5zLmNvbQ",
+    ]
+}
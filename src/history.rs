@@ -0,0 +1,258 @@
+//! Time-series persistence of scan results.
+//!
+//! A single `Scan`/`Analyze` pass is a snapshot, which under-reports the
+//! Iran-specific patterns that are fundamentally time-dependent (ICMP rate
+//! limiting only shows up after a few pings, UDP throttling only shows up
+//! once a transfer has run for a while). This module appends every scan
+//! cycle to an on-disk, append-only JSON-lines log and keeps a bounded
+//! in-memory window per protocol so the live view and `run_analyze` can talk
+//! about trends ("degraded over the last 10 minutes") instead of only the
+//! instantaneous score.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+const DEFAULT_HISTORY_PATH: &str = "ham_history.jsonl";
+/// Matches the live view's "last 10 minutes" framing at the default 2s scan
+/// cadence used by `monitor_protocols`.
+const WINDOW_CAPACITY: usize = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProtocolSample {
+    pub name: String,
+    pub score: u8,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCycle {
+    /// Seconds since the Unix epoch. Passed in by the caller rather than
+    /// read from the clock here so callers control how timestamps are
+    /// sourced (and so this module stays trivially testable).
+    pub timestamp_secs: u64,
+    pub protocols: Vec<ProtocolSample>,
+    pub iran_indicators: Vec<(String, String)>,
+}
+
+/// Appends `cycle` to the on-disk history store as one JSON line.
+pub async fn record_cycle(cycle: &ScanCycle, path: impl AsRef<Path>) -> std::io::Result<()> {
+    let line = serde_json::to_string(cycle).map_err(std::io::Error::other)?;
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(line.as_bytes()).await?;
+    file.write_all(b"\n").await?;
+    Ok(())
+}
+
+/// Reads every recorded cycle back from the history store, in order.
+pub async fn load_cycles(path: impl AsRef<Path>) -> std::io::Result<Vec<ScanCycle>> {
+    let content = match tokio::fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut cycles = Vec::new();
+    for line in content.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(cycle) = serde_json::from_str(line) {
+            cycles.push(cycle);
+        }
+    }
+    Ok(cycles)
+}
+
+pub fn default_history_path() -> PathBuf {
+    PathBuf::from(DEFAULT_HISTORY_PATH)
+}
+
+/// A bounded, per-protocol rolling window of recent scores.
+///
+/// Eviction follows a CLOCK-Pro-style second-chance scheme rather than a
+/// plain FIFO: each protocol's window carries a "hot" reference bit that is
+/// set whenever a fresh sample is recorded for it, and when the overall
+/// cache is full we evict the first *cold* protocol the clock hand reaches,
+/// giving protocols that are actively being scanned a second chance before
+/// one that hasn't reported in a while gets dropped.
+pub struct RollingHistory {
+    windows: HashMap<String, VecDeque<u8>>,
+    clock_hand: VecDeque<String>,
+    hot: HashMap<String, bool>,
+    capacity_per_protocol: usize,
+    max_protocols: usize,
+}
+
+impl RollingHistory {
+    pub fn new(max_protocols: usize) -> Self {
+        Self {
+            windows: HashMap::new(),
+            clock_hand: VecDeque::new(),
+            hot: HashMap::new(),
+            capacity_per_protocol: WINDOW_CAPACITY,
+            max_protocols,
+        }
+    }
+
+    pub fn record(&mut self, protocol: &str, score: u8) {
+        if !self.windows.contains_key(protocol) {
+            self.admit(protocol);
+        }
+        self.hot.insert(protocol.to_string(), true);
+
+        let window = self.windows.entry(protocol.to_string()).or_default();
+        window.push_back(score);
+        if window.len() > self.capacity_per_protocol {
+            window.pop_front();
+        }
+    }
+
+    fn admit(&mut self, protocol: &str) {
+        while self.windows.len() >= self.max_protocols {
+            if !self.evict_one() {
+                break;
+            }
+        }
+        self.windows.insert(protocol.to_string(), VecDeque::new());
+        self.clock_hand.push_back(protocol.to_string());
+    }
+
+    /// Sweeps the clock hand looking for a cold protocol to evict, giving
+    /// hot ones a second chance by clearing their bit instead of evicting.
+    fn evict_one(&mut self) -> bool {
+        let sweep_limit = self.clock_hand.len().max(1) * 2;
+        for _ in 0..sweep_limit {
+            let Some(candidate) = self.clock_hand.pop_front() else {
+                return false;
+            };
+            if self.hot.get(&candidate).copied().unwrap_or(false) {
+                self.hot.insert(candidate.clone(), false);
+                self.clock_hand.push_back(candidate);
+                continue;
+            }
+            self.windows.remove(&candidate);
+            self.hot.remove(&candidate);
+            return true;
+        }
+        false
+    }
+
+    /// Average score for `protocol` over whatever history is currently
+    /// held, or `None` if nothing has been recorded for it.
+    pub fn average(&self, protocol: &str) -> Option<f32> {
+        let window = self.windows.get(protocol)?;
+        if window.is_empty() {
+            return None;
+        }
+        Some(window.iter().map(|&s| s as f32).sum::<f32>() / window.len() as f32)
+    }
+
+    /// True when the most recent sample is markedly worse than the window's
+    /// average, i.e. the protocol has degraded over the tracked period.
+    pub fn is_degraded(&self, protocol: &str) -> bool {
+        let Some(window) = self.windows.get(protocol) else {
+            return false;
+        };
+        let Some(&latest) = window.back() else {
+            return false;
+        };
+        match self.average(protocol) {
+            Some(avg) => (avg - latest as f32) >= 3.0,
+            None => false,
+        }
+    }
+}
+
+/// Renders the recorded history as a per-protocol trend report, in the same
+/// terse style as the other `run_*` commands.
+pub async fn run_history() {
+    use colored::*;
+
+    let path = default_history_path();
+    let cycles = match load_cycles(&path).await {
+        Ok(cycles) => cycles,
+        Err(e) => {
+            println!("Could not read history store at {}: {e}", path.display());
+            return;
+        }
+    };
+
+    if cycles.is_empty() {
+        println!("No scan history recorded yet - run `ham scan` or `ham serve` for a while first.");
+        return;
+    }
+
+    println!("{}", "HAM Scan History".cyan().bold());
+    println!("{} cycle(s) recorded in {}\n", cycles.len(), path.display());
+
+    let mut window = RollingHistory::new(32);
+    for cycle in &cycles {
+        for sample in &cycle.protocols {
+            window.record(&sample.name, sample.score);
+        }
+    }
+
+    let mut protocol_names: Vec<&str> = cycles
+        .iter()
+        .flat_map(|c| c.protocols.iter().map(|p| p.name.as_str()))
+        .collect();
+    protocol_names.sort_unstable();
+    protocol_names.dedup();
+
+    for name in protocol_names {
+        let avg = window.average(name).unwrap_or(0.0);
+        let degraded = window.is_degraded(name);
+        let samples = cycles
+            .iter()
+            .filter(|c| c.protocols.iter().any(|p| p.name == name))
+            .count()
+            .min(WINDOW_CAPACITY);
+        let line = format!("{name:10} avg={avg:.1}/10 over {samples} sample(s)");
+        if degraded {
+            println!("   ⚠ {} - degraded vs. window average", line.yellow());
+        } else {
+            println!("   • {}", line);
+        }
+    }
+
+    if let (Some(first), Some(last)) = (cycles.first(), cycles.last()) {
+        for (name, before, after) in regressions(first, last) {
+            println!(
+                "   📉 {} regressed from {}/10 to {}/10 since the first recorded cycle",
+                name.red(),
+                before,
+                after
+            );
+        }
+    }
+}
+
+/// Compares the current cycle's scores against `baseline` (both callers pass
+/// the first recorded cycle) and returns the protocols whose score dropped by
+/// 2 or more.
+pub fn regressions(baseline: &ScanCycle, current: &ScanCycle) -> Vec<(String, u8, u8)> {
+    let baseline_scores: HashMap<&str, u8> = baseline
+        .protocols
+        .iter()
+        .map(|p| (p.name.as_str(), p.score))
+        .collect();
+
+    current
+        .protocols
+        .iter()
+        .filter_map(|p| {
+            let before = *baseline_scores.get(p.name.as_str())?;
+            if before >= p.score + 2 {
+                Some((p.name.clone(), before, p.score))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,276 @@
+//! LEDBAT-style (RFC 6817) reliable-UDP upload throughput measurement.
+//!
+//! The old upload-bandwidth logic just clamped `basic_udp` to a constant
+//! once it crossed a threshold. This module actually sends a stream of
+//! timestamped UDP packets to a cooperating echo endpoint, reads back the
+//! echoed timestamps to compute one-way delay, and drives a LEDBAT
+//! congestion window so the flow yields to competing traffic the way a real
+//! low-priority bulk transfer would - then reports the sustained goodput.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+/// How long we wait for a packet's echo before treating it as lost. This is
+/// deliberately generous since loss is inferred purely from silence, not a
+/// retransmit timer tuned to measured RTT.
+const ACK_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often we poll for both incoming acks and timed-out packets while the
+/// window still has room to send more.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+const TARGET_QUEUING_DELAY: Duration = Duration::from_millis(100);
+const GAIN: f64 = 1.0;
+const MSS: f64 = 1200.0; // bytes, matches our fixed packet payload size below
+const MIN_CWND: f64 = MSS;
+/// Rolling minimum window for `base_delay`, per RFC 6817 guidance.
+const BASE_DELAY_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone)]
+pub struct LedbatResult {
+    pub goodput_mbps: f64,
+    pub queuing_delay_samples_ms: Vec<f64>,
+    pub packets_sent: u32,
+    pub packets_lost: u32,
+}
+
+/// One one-way delay sample (the receiver's echoed receive time minus our
+/// send time) and when we recorded it. A constant offset between the two
+/// clocks cancels out of the queuing delay, which only uses relative minima;
+/// samples where the receiver's clock reads earlier than ours are discarded.
+struct DelaySample {
+    at: Instant,
+    one_way_delay: Duration,
+}
+
+struct DelayHistory {
+    samples: VecDeque<DelaySample>,
+    window: Duration,
+}
+
+impl DelayHistory {
+    fn new(window: Duration) -> Self {
+        Self { samples: VecDeque::new(), window }
+    }
+
+    fn push(&mut self, one_way_delay: Duration) {
+        let now = Instant::now();
+        self.samples.push_back(DelaySample { at: now, one_way_delay });
+        while let Some(front) = self.samples.front() {
+            if now.duration_since(front.at) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn minimum(&self) -> Option<Duration> {
+        self.samples.iter().map(|s| s.one_way_delay).min()
+    }
+
+    /// Minimum over just the most recent few samples, used as `current_delay`.
+    fn recent_minimum(&self, recent: usize) -> Option<Duration> {
+        self.samples.iter().rev().take(recent).map(|s| s.one_way_delay).min()
+    }
+}
+
+/// An outstanding packet we've sent but haven't heard an echo for yet.
+struct InFlight {
+    sent_at: Instant,
+    len: usize,
+}
+
+/// Runs a LEDBAT upload probe against `addr`, which must be running the
+/// matching echo responder (see [`run_echo_responder`]), for `duration`.
+///
+/// Unlike a naive sender that awaits each packet's ack before sending the
+/// next (stop-and-wait, which never lets more than one packet ride the
+/// path at a time regardless of `cwnd`), this keeps up to `cwnd` bytes
+/// genuinely in flight: it fires packets whenever the window has room,
+/// polling for acks (or newly-expired packets) in between sends rather
+/// than blocking on any single one.
+///
+/// Edge cases: `cwnd` is clamped to a minimum of one MSS so a congested path
+/// never stalls the flow entirely, `base_delay` history resets whenever we
+/// detect a route change (a sudden drop in delay below the current minimum,
+/// which RFC 6817 treats the same way), and a packet whose echo doesn't
+/// arrive within [`ACK_TIMEOUT`] is treated as lost rather than retried.
+pub async fn measure_upload(addr: &str, duration: Duration) -> std::io::Result<LedbatResult> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(addr).await?;
+
+    let mut cwnd = MIN_CWND;
+    let mut base_delay = DelayHistory::new(BASE_DELAY_WINDOW);
+    let mut queuing_delay_samples = Vec::new();
+    let mut bytes_acked_total: u64 = 0;
+    let mut packets_sent = 0u32;
+    let mut packets_lost = 0u32;
+    let mut seq: u32 = 0;
+
+    let mut outstanding: HashMap<u32, InFlight> = HashMap::new();
+    let mut bytes_in_flight: f64 = 0.0;
+
+    let started = Instant::now();
+    while started.elapsed() < duration || !outstanding.is_empty() {
+        // Fill the window: keep sending while there's room and time left.
+        while bytes_in_flight + MSS <= cwnd && started.elapsed() < duration {
+            let send_time = Instant::now();
+            let packet = build_packet(seq, send_time, started);
+            let this_seq = seq;
+            seq = seq.wrapping_add(1);
+            packets_sent += 1;
+
+            if socket.send(&packet).await.is_err() {
+                packets_lost += 1;
+                continue;
+            }
+            bytes_in_flight += packet.len() as f64;
+            outstanding.insert(this_seq, InFlight { sent_at: send_time, len: packet.len() });
+        }
+
+        if outstanding.is_empty() {
+            break;
+        }
+
+        // Wait for the next ack to arrive without blocking out further
+        // sends once one lands - the outer loop immediately tries to top
+        // the window back up after processing it.
+        let mut buf = [0u8; 32];
+        match timeout(POLL_INTERVAL, socket.recv(&mut buf)).await {
+            Ok(Ok(n)) if n >= 20 => {
+                let acked_seq = u32::from_be_bytes(buf[0..4].try_into().unwrap());
+                let echoed_send_ms = u64::from_be_bytes(buf[4..12].try_into().unwrap());
+                let recv_ms = u64::from_be_bytes(buf[12..20].try_into().unwrap());
+                let Some(packet) = outstanding.remove(&acked_seq) else {
+                    continue;
+                };
+                bytes_in_flight -= packet.len as f64;
+                bytes_acked_total += packet.len as u64;
+
+                if recv_ms >= echoed_send_ms {
+                    let one_way_delay = Duration::from_millis(recv_ms - echoed_send_ms);
+
+                    // Route-change heuristic: a sample well below the
+                    // current minimum suggests the path itself changed
+                    // rather than congestion easing, so start the
+                    // rolling minimum over from here.
+                    if let Some(min) = base_delay.minimum() {
+                        if one_way_delay + Duration::from_millis(20) < min {
+                            base_delay = DelayHistory::new(BASE_DELAY_WINDOW);
+                        }
+                    }
+                    base_delay.push(one_way_delay);
+
+                    let current_delay = base_delay.recent_minimum(8).unwrap_or(one_way_delay);
+                    let base = base_delay.minimum().unwrap_or(current_delay);
+                    let queuing_delay = current_delay.saturating_sub(base);
+                    queuing_delay_samples.push(queuing_delay.as_secs_f64() * 1000.0);
+
+                    let off_target = (TARGET_QUEUING_DELAY.as_secs_f64() - queuing_delay.as_secs_f64())
+                        / TARGET_QUEUING_DELAY.as_secs_f64();
+                    let bytes_acked = packet.len as f64;
+                    cwnd += GAIN * off_target * bytes_acked * MSS / cwnd;
+                    cwnd = cwnd.max(MIN_CWND);
+                }
+            }
+            Ok(Ok(_)) => {} // malformed ack, ignore and keep polling
+            Ok(Err(_)) => break,
+            Err(_) => {
+                // Nothing arrived this tick - reap anything that's aged
+                // past the ack timeout as lost before looping back to top
+                // the window up again.
+                let now = Instant::now();
+                let timed_out: Vec<u32> = outstanding
+                    .iter()
+                    .filter(|(_, p)| now.duration_since(p.sent_at) >= ACK_TIMEOUT)
+                    .map(|(seq, _)| *seq)
+                    .collect();
+                for seq in timed_out {
+                    if let Some(packet) = outstanding.remove(&seq) {
+                        bytes_in_flight -= packet.len as f64;
+                        packets_lost += 1;
+                        // Missing echo is treated as loss: halve the
+                        // window the way a standard congestion-controlled
+                        // sender would.
+                        cwnd = (cwnd / 2.0).max(MIN_CWND);
+                    }
+                }
+            }
+        }
+    }
+
+    let elapsed = started.elapsed().as_secs_f64().max(0.001);
+    let goodput_mbps = (bytes_acked_total as f64 * 8.0) / elapsed / 1_000_000.0;
+
+    Ok(LedbatResult {
+        goodput_mbps,
+        queuing_delay_samples_ms: queuing_delay_samples,
+        packets_sent,
+        packets_lost,
+    })
+}
+
+fn build_packet(seq: u32, _send_time: Instant, _epoch: Instant) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(MSS as usize);
+    packet.extend_from_slice(&seq.to_be_bytes());
+    packet.extend_from_slice(&wall_clock_ms().to_be_bytes());
+    packet.resize(MSS as usize, 0);
+    packet
+}
+
+/// Milliseconds since the Unix epoch on the local wall clock. Both the
+/// sender and the echo responder stamp packets with this, which only makes
+/// one-way delay meaningful if the two clocks are reasonably in sync (as
+/// they would be on NTP-synced hosts) - an accepted simplification for a
+/// heuristic probe rather than a precision timing tool.
+fn wall_clock_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Runs the matching echo responder that [`measure_upload`] needs on the
+/// other end: for every datagram received, stamp it with our own receive
+/// time and echo the sequence number and both timestamps straight back so
+/// the sender can match the ack to the right in-flight packet. HAM
+/// instances that want to measure each other's upload path run this
+/// side-by-side with a probing client pointed at them.
+pub async fn run_echo_responder(bind_addr: &str) -> std::io::Result<()> {
+    let socket = UdpSocket::bind(bind_addr).await?;
+    let mut buf = [0u8; 2048];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        if len < 12 {
+            continue;
+        }
+        let seq = &buf[0..4];
+        let send_ms = u64::from_be_bytes(buf[4..12].try_into().unwrap());
+        let recv_ms = wall_clock_ms();
+        let mut reply = Vec::with_capacity(20);
+        reply.extend_from_slice(seq);
+        reply.extend_from_slice(&send_ms.to_be_bytes());
+        reply.extend_from_slice(&recv_ms.to_be_bytes());
+        let _ = socket.send_to(&reply, peer).await;
+    }
+}
+
+/// Maps a measured upload rate onto HAM's 0-10 scoring scale, consistent
+/// with the thresholds the Iran analysis already describes (1-2 Mbps
+/// indicates throttling, >2.5 Mbps looks unrestricted).
+pub fn score_goodput(goodput_mbps: f64) -> u8 {
+    if goodput_mbps >= 5.0 {
+        10
+    } else if goodput_mbps >= 2.5 {
+        8
+    } else if goodput_mbps >= 1.5 {
+        5
+    } else if goodput_mbps >= 0.5 {
+        3
+    } else {
+        1
+    }
+}
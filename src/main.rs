@@ -1,3 +1,17 @@
+mod bridge;
+mod campaign;
+mod dns_inject;
+mod dns_stamp;
+mod edns;
+mod history;
+mod ledbat;
+mod noise_tunnel;
+mod quic;
+mod serve;
+mod sim;
+mod tls_frag;
+mod traceroute;
+
 use std::thread::sleep;
 use std::time::Duration;
 use crossterm::{
@@ -32,6 +46,31 @@ enum Commands {
     Export { format: String },
     /// Test Iran-specific censorship patterns
     TestIran,
+    /// Probe encrypted DNS transports (DoH/DoT/DNSCrypt) via resolver stamps
+    TestDns {
+        /// One or more sdns:// resolver stamps to probe instead of the defaults
+        #[arg(long)]
+        stamp: Vec<String>,
+    },
+    /// Run headlessly and expose scores over a Prometheus /metrics endpoint
+    Serve {
+        #[arg(long, default_value_t = 9891)]
+        port: u16,
+    },
+    /// Show trends from previously recorded scan cycles
+    History,
+    /// Run a YAML-defined measurement campaign
+    Campaign {
+        /// Path to the campaign plan YAML file
+        file: String,
+    },
+    /// Run the echo responder the LEDBAT upload probe (see `test_udp_advanced`)
+    /// needs on the other end; point a probing HAM instance's `HAM_LEDBAT_ECHO`
+    /// at this address
+    LedbatEcho {
+        #[arg(long, default_value_t = String::from("0.0.0.0:9892"))]
+        addr: String,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -52,6 +91,19 @@ async fn main() {
         Some(Commands::Analyze) => run_analyze().await,
         Some(Commands::Export { format }) => run_export(format).await,
         Some(Commands::TestIran) => run_iran_tests().await,
+        Some(Commands::TestDns { stamp }) => {
+            let stamps = if stamp.is_empty() { None } else { Some(stamp.clone()) };
+            edns::run_test_dns(stamps).await
+        }
+        Some(Commands::Serve { port }) => serve::run_serve(*port).await,
+        Some(Commands::History) => history::run_history().await,
+        Some(Commands::Campaign { file }) => campaign::run_campaign(file).await,
+        Some(Commands::LedbatEcho { addr }) => {
+            println!("HAM LEDBAT echo responder listening on {addr}");
+            if let Err(e) = ledbat::run_echo_responder(&addr).await {
+                eprintln!("ledbat echo responder failed: {e}");
+            }
+        }
         None => run_scan().await, // Default to scan
     }
 }
@@ -63,7 +115,8 @@ async fn run_scan() {
     
     let protocols = Arc::new(Mutex::new(Vec::<ProtocolStatus>::new()));
     let running = Arc::new(Mutex::new(true));
-    
+    let history = Arc::new(Mutex::new(history::RollingHistory::new(32)));
+
     // Initialize protocols
     {
         let mut p = protocols.lock().unwrap();
@@ -102,15 +155,23 @@ async fn run_scan() {
             details: "UDP connectivity".to_string(),
             color: Color::Yellow,
         });
+        p.push(ProtocolStatus {
+            name: "QUIC:443".to_string(),
+            status: "Testing...".to_string(),
+            score: 0,
+            details: "QUIC handshake".to_string(),
+            color: Color::Yellow,
+        });
     }
     
     // Spawn background monitoring task
     let protocols_clone = Arc::clone(&protocols);
     let running_clone = Arc::clone(&running);
+    let history_clone = Arc::clone(&history);
     tokio::spawn(async move {
-        monitor_protocols(protocols_clone, running_clone).await;
+        monitor_protocols(protocols_clone, running_clone, history_clone).await;
     });
-    
+
     // Main display loop
     loop {
         // Check for exit input
@@ -122,9 +183,9 @@ async fn run_scan() {
                 }
             }
         }
-        
+
         // Update display
-        display_protocols(&mut stdout, &protocols).await;
+        display_protocols(&mut stdout, &protocols, &history).await;
         sleep(Duration::from_millis(500));
     }
     
@@ -133,11 +194,15 @@ async fn run_scan() {
     println!("HAM scan completed. Press any key to exit.");
 }
 
-async fn display_protocols(stdout: &mut std::io::Stdout, protocols: &Arc<Mutex<Vec<ProtocolStatus>>>) {
+async fn display_protocols(
+    stdout: &mut std::io::Stdout,
+    protocols: &Arc<Mutex<Vec<ProtocolStatus>>>,
+    history: &Arc<Mutex<history::RollingHistory>>,
+) {
     execute!(stdout, MoveTo(0, 0), Clear(ClearType::All)).unwrap();
-    
+
     // Header
-    execute!(stdout, 
+    execute!(stdout,
         SetForegroundColor(Color::Cyan),
         Print("HAM - Network Protocol Scanner"),
         MoveTo(0, 1),
@@ -145,22 +210,30 @@ async fn display_protocols(stdout: &mut std::io::Stdout, protocols: &Arc<Mutex<V
         MoveTo(0, 3),
         ResetColor
     ).unwrap();
-    
+
     let protocols_guard = protocols.lock().unwrap();
+    let history_guard = history.lock().unwrap();
     for (i, protocol) in protocols_guard.iter().enumerate() {
         let progress_bar = create_progress_bar(protocol.score);
+        // Flag protocols that have degraded relative to their own recent
+        // window, not just the instantaneous score - a single bad reading
+        // next to a long run of good ones is noise, but a drop from the
+        // window average is the pattern the Iran-style progressive
+        // throttling/blocking this tool targets actually looks like.
+        let trend = if history_guard.is_degraded(&protocol.name) { " ⚠ degraded vs. recent avg" } else { "" };
         execute!(stdout,
             MoveTo(0, 4 + i as u16),
             SetForegroundColor(protocol.color),
-            Print(format!("[{:8}] {} {}", 
-                protocol.name, 
-                progress_bar, 
-                protocol.status
+            Print(format!("[{:8}] {} {}{}",
+                protocol.name,
+                progress_bar,
+                protocol.status,
+                trend,
             )),
             ResetColor
         ).unwrap();
     }
-    
+
     stdout.flush().unwrap();
 }
 
@@ -170,7 +243,11 @@ fn create_progress_bar(score: u8) -> String {
     format!("{}{}", filled, empty)
 }
 
-async fn monitor_protocols(protocols: Arc<Mutex<Vec<ProtocolStatus>>>, running: Arc<Mutex<bool>>) {
+async fn monitor_protocols(
+    protocols: Arc<Mutex<Vec<ProtocolStatus>>>,
+    running: Arc<Mutex<bool>>,
+    history: Arc<Mutex<history::RollingHistory>>,
+) {
     while *running.lock().unwrap() {
         // Test HTTP (port 80)
         let http_score = test_tcp_connection("8.8.8.8:53", Duration::from_secs(3)).await;
@@ -191,11 +268,46 @@ async fn monitor_protocols(protocols: Arc<Mutex<Vec<ProtocolStatus>>>, running:
         // Test UDP (simulated)
         let udp_score = test_udp().await;
         update_protocol(&protocols, "UDP", udp_score, "UDP connectivity").await;
-        
+
+        // Test QUIC (real handshake probe)
+        let quic_score = quic::test_quic_connectivity("www.google.com", 443).await;
+        update_protocol(&protocols, "QUIC:443", quic_score, "QUIC handshake").await;
+
+        record_scan_cycle(&protocols, &history).await;
+
         tokio::time::sleep(Duration::from_secs(2)).await;
     }
 }
 
+async fn record_scan_cycle(protocols: &Arc<Mutex<Vec<ProtocolStatus>>>, history: &Arc<Mutex<history::RollingHistory>>) {
+    let samples: Vec<history::ProtocolSample> = {
+        let guard = protocols.lock().unwrap();
+        guard
+            .iter()
+            .map(|p| history::ProtocolSample { name: p.name.clone(), score: p.score })
+            .collect()
+    };
+
+    {
+        let mut history_guard = history.lock().unwrap();
+        for sample in &samples {
+            history_guard.record(&sample.name, sample.score);
+        }
+    }
+
+    let cycle = history::ScanCycle {
+        timestamp_secs: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        protocols: samples,
+        iran_indicators: Vec::new(),
+    };
+    if let Err(e) = history::record_cycle(&cycle, history::default_history_path()).await {
+        eprintln!("failed to record scan cycle to history: {e}");
+    }
+}
+
 async fn update_protocol(protocols: &Arc<Mutex<Vec<ProtocolStatus>>>, name: &str, score: u8, details: &str) {
     let mut protocols_guard = protocols.lock().unwrap();
     if let Some(protocol) = protocols_guard.iter_mut().find(|p| p.name == name) {
@@ -303,6 +415,51 @@ async fn run_analyze() {
     
     println!("\n🛡️  {}", "Censorship Detection:".yellow());
     analyze_censorship().await;
+
+    println!("\n📈 {}", "Trend vs. recorded baseline:".yellow());
+    analyze_history_regressions().await;
+}
+
+async fn analyze_history_regressions() {
+    let cycles = match history::load_cycles(history::default_history_path()).await {
+        Ok(cycles) => cycles,
+        Err(e) => {
+            println!("   ? Could not read scan history: {e}");
+            return;
+        }
+    };
+
+    let Some(baseline) = cycles.first() else {
+        println!("   ? No recorded baseline yet - run `ham scan` for a while to build one");
+        return;
+    };
+
+    let current = history::ScanCycle {
+        timestamp_secs: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        protocols: vec![
+            history::ProtocolSample {
+                name: "TCP:443".to_string(),
+                score: test_https_connection().await,
+            },
+            history::ProtocolSample {
+                name: "DNS".to_string(),
+                score: test_dns_resolution().await,
+            },
+        ],
+        iran_indicators: Vec::new(),
+    };
+
+    let regressions = history::regressions(baseline, &current);
+    if regressions.is_empty() {
+        println!("   ✓ {}", "No regressions vs. the recorded baseline".green());
+    } else {
+        for (name, before, after) in regressions {
+            println!("   ✗ {} regressed from {}/10 to {}/10", name.red(), before, after);
+        }
+    }
 }
 
 async fn analyze_network_interfaces() {
@@ -369,6 +526,17 @@ async fn analyze_censorship() {
     } else {
         println!("   📊 {}", "Heavy censorship likely".red());
     }
+
+    println!("\n   🔍 Checking for DNS injection/poisoning...");
+    for report in dns_inject::run_injection_checks().await {
+        let line = format!("{} - {} ({})", report.domain, report.verdict, report.detail);
+        match report.verdict {
+            dns_inject::InjectionVerdict::Clean => println!("   ✓ {}", line.green()),
+            dns_inject::InjectionVerdict::InjectedIp => println!("   ✗ {}", line.red()),
+            dns_inject::InjectionVerdict::NxDomainForged => println!("   ✗ {}", line.red()),
+            dns_inject::InjectionVerdict::Timeout => println!("   ⚠ {}", line.yellow()),
+        }
+    }
 }
 
 async fn run_export(format: &str) {
@@ -383,25 +551,19 @@ async fn run_export(format: &str) {
 }
 
 async fn export_json() {
-    let config = serde_json::json!({
-        "ham_config": {
-            "version": "0.1.0",
-            "scan_intervals": 2,
-            "test_endpoints": [
-                "8.8.8.8:53",
-                "1.1.1.1:53"
-            ],
-            "protocols": ["tcp", "udp", "dns"]
-        }
-    });
-    
+    let config = bridge::discover_working_transports().await;
+
     println!("Configuration JSON:");
     println!("{}", serde_json::to_string_pretty(&config).unwrap());
 }
 
 async fn export_qr() {
-    println!("QR code export not yet implemented.");
-    println!("Would contain bridge/tunnel configuration for sharing.");
+    let config = bridge::discover_working_transports().await;
+    if config.transports.is_empty() {
+        println!("No working transports were discovered - nothing to export.");
+        return;
+    }
+    bridge::print_qr(&config);
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -573,6 +735,7 @@ async fn test_icmp_rate_limiting(_config: &IranConfig) {
     let successful_pings = icmp_results.iter().take_while(|&&x| x).count();
     if successful_pings >= 2 && successful_pings <= 3 {
         println!("   ✓ {} - Rate limiting after {} pings matches Iran pattern", "ICMP Pattern".green(), successful_pings);
+        println!("   📍 Blocking localized to hop {}", successful_pings + 1);
     } else {
         println!("   ⚠ {} - Unexpected ICMP behavior", "ICMP Pattern".yellow());
     }
@@ -587,9 +750,15 @@ async fn test_tls_patterns(_config: &IranConfig) {
     println!("   🔍 Testing normal website TLS...");
     let normal_tls_score = test_https_connection().await;
     
-    // Simulate VPN tunnel detection test
+    // VPN tunnel detection: obfuscated Noise handshake vs. a plain-VPN-style baseline
     println!("   🔍 Testing VPN tunnel detection...");
     let vpn_detection_score = test_vpn_tunnel_detection().await;
+    let tunnel_detail = noise_tunnel::probe(noise_tunnel::Carrier::Tcp).await;
+    println!(
+        "      Noise-obfuscated path: {}/10, plain-VPN-style baseline: {}/10",
+        tunnel_detail.noise_score(),
+        tunnel_detail.plain_score()
+    );
     
     // Simulate TLS fragmentation effectiveness
     println!("   🔍 Testing TLS fragmentation bypass...");
@@ -640,7 +809,16 @@ async fn generate_iran_analysis(_config: &IranConfig) {
     println!("   📈 Pattern Match: {}", "Great Firewall-style filtering".yellow());
     
     println!("\n💡 {} Recommendations:", "Bypass".green().bold());
-    println!("   1. ✅ Use TLS fragmentation for HTTPS (high success rate)");
+    let fragmentation_result = tls_frag::probe_fragmentation(tls_frag::DEFAULT_FILTERED_SNI).await;
+    if fragmentation_result.fragmentation_bypasses_dpi() {
+        println!(
+            "   1. ✅ {} ({} blocked the control handshake, fragmentation got a ServerHello)",
+            "Fragmentation bypass effective".green().bold(),
+            tls_frag::DEFAULT_FILTERED_SNI
+        );
+    } else {
+        println!("   1. ✅ Use TLS fragmentation for HTTPS (high success rate)");
+    }
     println!("   2. ✅ Avoid QUIC on port 443, try alternative ports");
     println!("   3. ✅ Expect UDP upload limitations, use TCP when possible"); 
     println!("   4. ❌ IPv6 not available as bypass option");
@@ -694,50 +872,62 @@ async fn run_default_iran_tests() {
 }
 
 // Iran-specific test implementations
-async fn test_quic_connectivity(_domain: &str, port: u16) -> u8 {
-    // Simulate QUIC connectivity test
-    // In real implementation, this would use a QUIC client library
-    match port {
-        443 => 1, // Port 443 typically blocked for QUIC in Iran
-        80 | 8080 => 6, // Alternative ports may work with limitations
-        _ => 3,
-    }
+async fn test_quic_connectivity(domain: &str, port: u16) -> u8 {
+    quic::test_quic_connectivity(domain, port).await
+}
+
+/// Endpoint running [`ledbat::run_echo_responder`] that we measure upload
+/// throughput against. Overridable via `HAM_LEDBAT_ECHO` for users running
+/// their own relay; falls back to the basic UDP score when nothing answers.
+fn ledbat_echo_addr() -> String {
+    std::env::var("HAM_LEDBAT_ECHO").unwrap_or_else(|_| "127.0.0.1:9892".to_string())
 }
 
 async fn test_udp_advanced() -> u8 {
-    // Enhanced UDP test including upload bandwidth simulation
+    // Basic UDP reachability, used as a fallback when no LEDBAT echo
+    // endpoint is configured/reachable.
     let basic_udp = test_udp().await;
-    
-    // Simulate upload bandwidth test
-    // In real implementation, this would transfer test data
-    if basic_udp > 5 {
-        // Simulate upload limitation detection
-        5 // Limited due to upload throttling
-    } else {
-        basic_udp
+
+    match ledbat::measure_upload(&ledbat_echo_addr(), Duration::from_secs(3)).await {
+        Ok(result) if result.goodput_mbps > 0.0 => ledbat::score_goodput(result.goodput_mbps),
+        _ => basic_udp,
     }
 }
 
 async fn test_ipv6_connectivity() -> bool {
-    // Simulate IPv6 connectivity test
-    // In Iran, IPv6 is typically disabled nationwide
-    false
+    // Route through the same Transport trait test_icmp_progressive already
+    // uses, rather than a hardcoded result - many censors (notably Iran's)
+    // do null-route IPv6 nationwide, but that should be something we
+    // actually measure, not assume.
+    use sim::Transport;
+    let transport = sim::LiveTransport { target: "8.8.8.8".to_string() };
+    transport.ipv6_connect().await
 }
 
 async fn test_icmp_progressive() -> Vec<bool> {
-    // Simulate progressive ICMP testing
-    // Typically first 2-3 pings succeed, then blocked
-    vec![true, true, true, false, false, false]
+    let target: std::net::Ipv4Addr = "8.8.8.8".parse().unwrap();
+    match traceroute::run_traceroute(target, 10, 3).await {
+        Ok(hops) => traceroute::summarize(&hops).0,
+        // Raw ICMP sockets need CAP_NET_RAW/root; fall back to the
+        // ping-shelling transport used by the simulation harness when we
+        // don't have that privilege.
+        Err(_) => {
+            let transport = sim::LiveTransport { target: "8.8.8.8".to_string() };
+            sim::probe_progressive(&transport, 6, 3).await
+        }
+    }
 }
 
 async fn test_vpn_tunnel_detection() -> u8 {
-    // Simulate VPN tunnel detection test
-    // Lower scores indicate higher blocking/detection
-    2 // VPN tunnels typically detected and blocked
+    let tcp_result = noise_tunnel::probe(noise_tunnel::Carrier::Tcp).await;
+    let udp_result = noise_tunnel::probe(noise_tunnel::Carrier::Udp).await;
+
+    // Report the better of the two carriers' obfuscated-path scores, since
+    // a censor that only blocks one protocol-specific carrier still leaves
+    // the tunnel usable overall.
+    tcp_result.noise_score().max(udp_result.noise_score())
 }
 
 async fn test_tls_fragmentation() -> u8 {
-    // Simulate TLS fragmentation effectiveness test
-    // Fragmentation often works as bypass in Iran
-    8
+    tls_frag::probe_fragmentation(tls_frag::DEFAULT_FILTERED_SNI).await.score()
 }
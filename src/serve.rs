@@ -0,0 +1,170 @@
+//! Headless daemon mode: runs the same protocol monitoring loop as the
+//! interactive `Scan` view, but exposes the current scores over a tiny
+//! Prometheus-text `/metrics` endpoint instead of drawing a TUI. Scoring and
+//! testing are shared with `Scan` - only the presentation layer differs.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::history;
+use crate::{monitor_protocols, ProtocolStatus};
+
+/// How often the background task refreshes the DNS injection status that
+/// `/metrics` reports. The probe itself takes a couple of seconds (it waits
+/// out a collection window per domain), so it's refreshed on its own slow
+/// cadence rather than being run inline for every scrape.
+const INJECTION_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Runs the monitoring loop in the background and serves `/metrics` on
+/// `port` until the process is killed.
+pub async fn run_serve(port: u16) {
+    println!("HAM daemon starting - Prometheus metrics on http://0.0.0.0:{port}/metrics");
+
+    let protocols = Arc::new(Mutex::new(Vec::<ProtocolStatus>::new()));
+    let running = Arc::new(Mutex::new(true));
+    let injection_detected = Arc::new(Mutex::new(None::<bool>));
+    let history = Arc::new(Mutex::new(history::RollingHistory::new(32)));
+
+    {
+        let mut p = protocols.lock().unwrap();
+        for (name, details) in [
+            ("TCP:80", "HTTP connectivity"),
+            ("TCP:443", "HTTPS connectivity"),
+            ("DNS", "Domain resolution"),
+            ("PING", "ICMP connectivity"),
+            ("UDP", "UDP connectivity"),
+            ("QUIC:443", "QUIC handshake"),
+        ] {
+            p.push(ProtocolStatus {
+                name: name.to_string(),
+                status: "Testing...".to_string(),
+                score: 0,
+                details: details.to_string(),
+                color: crossterm::style::Color::Yellow,
+            });
+        }
+    }
+
+    let monitor_protocols_handle = Arc::clone(&protocols);
+    let monitor_running_handle = Arc::clone(&running);
+    let monitor_history_handle = Arc::clone(&history);
+    tokio::spawn(async move {
+        monitor_protocols(monitor_protocols_handle, monitor_running_handle, monitor_history_handle).await;
+    });
+
+    let injection_refresh_handle = Arc::clone(&injection_detected);
+    let injection_running_handle = Arc::clone(&running);
+    tokio::spawn(async move {
+        refresh_injection_status(injection_refresh_handle, injection_running_handle).await;
+    });
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("could not bind metrics listener on port {port}: {e}");
+            return;
+        }
+    };
+
+    loop {
+        let (socket, _) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("metrics listener accept failed: {e}");
+                continue;
+            }
+        };
+        let protocols = Arc::clone(&protocols);
+        let injection_detected = Arc::clone(&injection_detected);
+        let history = Arc::clone(&history);
+        tokio::spawn(async move {
+            handle_connection(socket, protocols, injection_detected, history).await;
+        });
+    }
+}
+
+/// Runs the DNS injection probe battery on [`INJECTION_CHECK_INTERVAL`] and
+/// stashes the verdict for `render_metrics` to read. The probe itself blocks
+/// for a few seconds (a collection window per domain), so it would stall
+/// every `/metrics` scrape if run inline on the request path instead.
+async fn refresh_injection_status(injection_detected: Arc<Mutex<Option<bool>>>, running: Arc<Mutex<bool>>) {
+    while *running.lock().unwrap() {
+        let detected = crate::dns_inject::run_injection_checks()
+            .await
+            .iter()
+            .any(|r| {
+                matches!(
+                    r.verdict,
+                    crate::dns_inject::InjectionVerdict::InjectedIp | crate::dns_inject::InjectionVerdict::NxDomainForged
+                )
+            });
+        *injection_detected.lock().unwrap() = Some(detected);
+        tokio::time::sleep(INJECTION_CHECK_INTERVAL).await;
+    }
+}
+
+async fn handle_connection(
+    mut socket: tokio::net::TcpStream,
+    protocols: Arc<Mutex<Vec<ProtocolStatus>>>,
+    injection_detected: Arc<Mutex<Option<bool>>>,
+    history: Arc<Mutex<history::RollingHistory>>,
+) {
+    let mut buf = [0u8; 1024];
+    // We only serve one endpoint and don't care about the request beyond
+    // "something connected"; read what's available and ignore the rest.
+    let _ = socket.read(&mut buf).await;
+
+    let body = render_metrics(&protocols, &injection_detected, &history);
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+fn render_metrics(
+    protocols: &Arc<Mutex<Vec<ProtocolStatus>>>,
+    injection_detected: &Arc<Mutex<Option<bool>>>,
+    history: &Arc<Mutex<history::RollingHistory>>,
+) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP ham_protocol_score Protocol reachability score (0-10).\n");
+    out.push_str("# TYPE ham_protocol_score gauge\n");
+
+    let scores: Vec<(String, u8)> = {
+        let guard = protocols.lock().unwrap();
+        guard.iter().map(|p| (p.name.clone(), p.score)).collect()
+    };
+    for (name, score) in &scores {
+        out.push_str(&format!("ham_protocol_score{{protocol=\"{name}\"}} {score}\n"));
+    }
+
+    // Surfaces the same "degraded vs. recent window average" trend the
+    // interactive Scan view now shows, so a headless `ham serve` deployment
+    // can alert on it too instead of only ever seeing the instantaneous
+    // score.
+    out.push_str("# HELP ham_protocol_degraded Whether a protocol's latest score is markedly worse than its recent rolling average (0/1).\n");
+    out.push_str("# TYPE ham_protocol_degraded gauge\n");
+    {
+        let history_guard = history.lock().unwrap();
+        for (name, _) in &scores {
+            let degraded = history_guard.is_degraded(name) as u8;
+            out.push_str(&format!("ham_protocol_degraded{{protocol=\"{name}\"}} {degraded}\n"));
+        }
+    }
+
+    // Only published once the background refresh has completed its first
+    // pass; omitted until then rather than reporting a misleading 0.
+    if let Some(detected) = *injection_detected.lock().unwrap() {
+        out.push_str("# HELP ham_dns_injection_detected Whether DNS injection was observed on the last background injection-probe pass (0/1).\n");
+        out.push_str("# TYPE ham_dns_injection_detected gauge\n");
+        out.push_str(&format!("ham_dns_injection_detected {}\n", detected as u8));
+    }
+
+    out
+}
@@ -0,0 +1,275 @@
+//! Deterministic censor simulation harness.
+//!
+//! All of HAM's `test_*` probes talk to the real network, which makes their
+//! detection logic impossible to validate deterministically - a flaky
+//! network, not a bug, could be why a test failed. This module provides a
+//! [`Censor`] state machine that deterministically applies simple filtering
+//! rules to a virtual network, and a [`Transport`] trait that both the real
+//! network and the simulator implement, so the exact same probe code can run
+//! against either.
+
+use std::time::Duration;
+
+/// A rule the simulated censor applies to classify or mangle traffic.
+#[derive(Debug, Clone)]
+pub enum CensorRule {
+    /// For a TTL-stepping probe: silently drop every hop probe once the
+    /// hop index reaches `n` (models progressive ICMP rate limiting).
+    DropAfterHop(u32),
+    /// For a throughput probe: any send at a rate above `bytes_per_sec` is
+    /// dropped outright, and any send at or below it gets through (models
+    /// upload throttling).
+    ThrottleAboveRate { bytes_per_sec: u64 },
+    /// For a TLS probe: reset the connection whenever the ClientHello's SNI
+    /// matches one of `hosts`, unless it arrives fragmented (models
+    /// single-packet SNI-matching DPI).
+    RstOnSniMatch { hosts: Vec<String> },
+    /// For an IPv6 probe: always fail IPv6 connectivity attempts (models a
+    /// nationwide null-route).
+    NullRouteIpv6,
+}
+
+/// A deterministic virtual adversary that a [`Transport`] implementation
+/// consults to decide how to answer a probe. It takes no random seed: the
+/// same rules always give the same answers.
+#[derive(Debug, Clone)]
+pub struct Censor {
+    rules: Vec<CensorRule>,
+}
+
+impl Censor {
+    pub fn new(rules: Vec<CensorRule>) -> Self {
+        Self { rules }
+    }
+
+    /// No filtering at all - useful as a baseline in tests.
+    pub fn transparent() -> Self {
+        Self { rules: vec![] }
+    }
+
+    fn drop_after_hop(&self) -> Option<u32> {
+        self.rules.iter().find_map(|r| match r {
+            CensorRule::DropAfterHop(n) => Some(*n),
+            _ => None,
+        })
+    }
+
+    fn throttle_rate(&self) -> Option<u64> {
+        self.rules.iter().find_map(|r| match r {
+            CensorRule::ThrottleAboveRate { bytes_per_sec } => Some(*bytes_per_sec),
+            _ => None,
+        })
+    }
+
+    fn sni_blocklist(&self) -> Option<&[String]> {
+        self.rules.iter().find_map(|r| match r {
+            CensorRule::RstOnSniMatch { hosts } => Some(hosts.as_slice()),
+            _ => None,
+        })
+    }
+
+    fn blocks_ipv6(&self) -> bool {
+        self.rules.iter().any(|r| matches!(r, CensorRule::NullRouteIpv6))
+    }
+}
+
+/// Outcome of probing a single TTL hop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HopProbeResult {
+    pub responded: bool,
+    pub rtt: Option<Duration>,
+}
+
+/// Abstracts the network operations HAM's probes need, so the exact same
+/// probe logic can run against the real network (via [`LiveTransport`]) or
+/// against [`SimulatedTransport`] in tests.
+#[async_trait::async_trait]
+pub trait Transport: Send + Sync {
+    /// Probe a single TTL hop, as used by the ICMP progressive/traceroute test.
+    async fn probe_hop(&self, ttl: u32) -> HopProbeResult;
+
+    /// Attempt to establish IPv6 connectivity to a well-known host.
+    async fn ipv6_connect(&self) -> bool;
+
+    /// Send at `bytes_per_sec` and report whether the traffic got through,
+    /// used to model throttle-above-rate.
+    async fn send_at_rate(&self, bytes_per_sec: u64) -> bool;
+
+    /// Attempt a TLS ClientHello for `sni`, optionally fragmented, and
+    /// report whether the simulated handshake would proceed.
+    async fn tls_client_hello(&self, sni: &str, fragmented: bool) -> bool;
+}
+
+/// A [`Transport`] backed entirely by a [`Censor`] state machine - no real
+/// sockets involved, so probes against it are fully deterministic.
+pub struct SimulatedTransport {
+    censor: Censor,
+}
+
+impl SimulatedTransport {
+    pub fn new(censor: Censor) -> Self {
+        Self { censor }
+    }
+}
+
+#[async_trait::async_trait]
+impl Transport for SimulatedTransport {
+    async fn probe_hop(&self, ttl: u32) -> HopProbeResult {
+        match self.censor.drop_after_hop() {
+            Some(cutoff) if ttl >= cutoff => HopProbeResult { responded: false, rtt: None },
+            _ => HopProbeResult { responded: true, rtt: Some(Duration::from_millis(10 * ttl as u64)) },
+        }
+    }
+
+    async fn ipv6_connect(&self) -> bool {
+        !self.censor.blocks_ipv6()
+    }
+
+    async fn send_at_rate(&self, bytes_per_sec: u64) -> bool {
+        match self.censor.throttle_rate() {
+            Some(limit) => bytes_per_sec <= limit,
+            None => true,
+        }
+    }
+
+    async fn tls_client_hello(&self, sni: &str, fragmented: bool) -> bool {
+        match self.censor.sni_blocklist() {
+            Some(hosts) if hosts.iter().any(|h| h == sni) => fragmented,
+            _ => true,
+        }
+    }
+}
+
+/// A [`Transport`] backed by the real network, so the exact same
+/// [`probe_progressive`] logic that tests exercise against
+/// [`SimulatedTransport`] also drives the live `TestIran` probes -
+/// `probe_hop` backs `test_icmp_progressive`'s non-root fallback and
+/// `ipv6_connect` backs `test_ipv6_connectivity` directly. `send_at_rate`
+/// and `tls_client_hello` wrap the same `ledbat`/`tls_frag` probes those
+/// two modules' own callers already use directly; they exist on this trait
+/// so `probe_progressive`-style deterministic tests can cover the
+/// throttle/SNI-reset detection logic the way this module's test suite
+/// does, without every direct caller needing to go through `Transport`.
+pub struct LiveTransport {
+    pub target: String,
+}
+
+#[async_trait::async_trait]
+impl Transport for LiveTransport {
+    async fn probe_hop(&self, ttl: u32) -> HopProbeResult {
+        use std::process::Command;
+        use std::time::Instant;
+
+        let started = Instant::now();
+        let output = Command::new("ping")
+            .args(["-c", "1", "-W", "1", "-t", &ttl.to_string(), &self.target])
+            .output();
+
+        match output {
+            Ok(out) if out.status.success() => HopProbeResult { responded: true, rtt: Some(started.elapsed()) },
+            _ => HopProbeResult { responded: false, rtt: None },
+        }
+    }
+
+    async fn ipv6_connect(&self) -> bool {
+        use std::time::Duration;
+        use tokio::net::TcpStream;
+        use tokio::time::timeout;
+        matches!(
+            timeout(Duration::from_secs(3), TcpStream::connect("[2001:4860:4860::8888]:53")).await,
+            Ok(Ok(_))
+        )
+    }
+
+    async fn send_at_rate(&self, bytes_per_sec: u64) -> bool {
+        let addr = format!("{}:9892", self.target);
+        match crate::ledbat::measure_upload(&addr, Duration::from_secs(1)).await {
+            Ok(result) => (result.goodput_mbps * 1_000_000.0 / 8.0) as u64 >= bytes_per_sec,
+            Err(_) => false,
+        }
+    }
+
+    async fn tls_client_hello(&self, sni: &str, fragmented: bool) -> bool {
+        let result = crate::tls_frag::probe_fragmentation(sni).await;
+        if fragmented {
+            result.score() > 1
+        } else {
+            result.control == crate::tls_frag::HandshakeOutcome::ServerHelloReceived
+        }
+    }
+}
+
+/// Drives [`Transport::probe_hop`] across `1..=max_ttl`, stopping once
+/// `silent_hop_run` consecutive hops fail to respond - the simulated
+/// equivalent of [`crate::traceroute::run_traceroute`], used so detection
+/// logic can be asserted deterministically in tests.
+pub async fn probe_progressive(transport: &dyn Transport, max_ttl: u32, silent_hop_run: u32) -> Vec<bool> {
+    let mut results = Vec::new();
+    let mut consecutive_silent = 0u32;
+
+    for ttl in 1..=max_ttl {
+        let hop = transport.probe_hop(ttl).await;
+        results.push(hop.responded);
+
+        if hop.responded {
+            consecutive_silent = 0;
+        } else {
+            consecutive_silent += 1;
+            if consecutive_silent >= silent_hop_run {
+                break;
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drop_after_hop_produces_the_configured_pattern() {
+        let censor = Censor::new(vec![CensorRule::DropAfterHop(3)]);
+        let transport = SimulatedTransport::new(censor);
+
+        let results = probe_progressive(&transport, 6, 3).await;
+
+        assert_eq!(results, vec![true, true, false, false, false]);
+    }
+
+    #[tokio::test]
+    async fn transparent_censor_never_drops() {
+        let transport = SimulatedTransport::new(Censor::transparent());
+
+        let results = probe_progressive(&transport, 5, 2).await;
+
+        assert_eq!(results, vec![true, true, true, true, true]);
+    }
+
+    #[tokio::test]
+    async fn null_route_blocks_ipv6() {
+        let transport = SimulatedTransport::new(Censor::new(vec![CensorRule::NullRouteIpv6]));
+
+        assert!(!transport.ipv6_connect().await);
+    }
+
+    #[tokio::test]
+    async fn rst_on_sni_match_is_defeated_by_fragmentation() {
+        let censor = Censor::new(vec![CensorRule::RstOnSniMatch { hosts: vec!["blocked.example".to_string()] }]);
+        let transport = SimulatedTransport::new(censor);
+
+        assert!(!transport.tls_client_hello("blocked.example", false).await);
+        assert!(transport.tls_client_hello("blocked.example", true).await);
+        assert!(transport.tls_client_hello("allowed.example", false).await);
+    }
+
+    #[tokio::test]
+    async fn throttle_above_rate_caps_throughput() {
+        let censor = Censor::new(vec![CensorRule::ThrottleAboveRate { bytes_per_sec: 1000 }]);
+        let transport = SimulatedTransport::new(censor);
+
+        assert!(transport.send_at_rate(500).await);
+        assert!(!transport.send_at_rate(2000).await);
+    }
+}
@@ -0,0 +1,279 @@
+//! Real TLS ClientHello fragmentation probing.
+//!
+//! `test_tls_fragmentation` used to return a canned score. This module opens
+//! an actual TCP connection to a filtered SNI, builds a minimal TLS 1.2
+//! ClientHello ourselves, and sends it using one of several fragmentation
+//! strategies so DPI cannot reassemble the SNI from a single segment/record.
+//! We compare each strategy's outcome against a normal, single-write,
+//! single-record control that is expected to be reset, so the result
+//! reflects which strategies actually bypass the filter rather than a fixed
+//! number.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+/// A TLS host that is known to be SNI-filtered in the target region; HAM
+/// uses this as the probe target for fragmentation effectiveness.
+pub const DEFAULT_FILTERED_SNI: &str = "twitter.com";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeOutcome {
+    /// Got back bytes that look like the start of a TLS ServerHello.
+    ServerHelloReceived,
+    /// Connection was refused, reset or closed before a full record header
+    /// arrived, or the reply was not a TLS handshake record.
+    Reset,
+    /// No data arrived before the deadline.
+    TimedOut,
+}
+
+/// A way of splitting the ClientHello across the wire so that DPI cannot
+/// see the SNI extension in one contiguous buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStrategy {
+    /// TCP-level segmentation: flush the bytes up to the midpoint of the
+    /// `server_name` value in one `write_all`/segment, pause briefly, then
+    /// write the rest
+    /// - defeats DPI that inspects only the first TCP segment.
+    TcpSegmentation,
+    /// TLS record-level splitting: emit the ClientHello as two distinct TLS
+    /// records, broken in the middle of the `server_name` value itself -
+    /// defeats DPI that reassembles at the TCP level but still expects a
+    /// single TLS record.
+    TlsRecordSplit,
+}
+
+impl SplitStrategy {
+    pub fn label(self) -> &'static str {
+        match self {
+            SplitStrategy::TcpSegmentation => "tcp-segmentation",
+            SplitStrategy::TlsRecordSplit => "tls-record-split",
+        }
+    }
+}
+
+const ALL_STRATEGIES: [SplitStrategy; 2] = [SplitStrategy::TcpSegmentation, SplitStrategy::TlsRecordSplit];
+
+#[derive(Debug, Clone)]
+pub struct StrategyResult {
+    pub strategy: SplitStrategy,
+    pub outcome: HandshakeOutcome,
+}
+
+#[derive(Debug, Clone)]
+pub struct FragmentationProbeResult {
+    pub control: HandshakeOutcome,
+    pub strategies: Vec<StrategyResult>,
+}
+
+impl FragmentationProbeResult {
+    fn successful_strategies(&self) -> usize {
+        self.strategies
+            .iter()
+            .filter(|s| s.outcome == HandshakeOutcome::ServerHelloReceived)
+            .count()
+    }
+
+    /// True when the control was blocked but at least one fragmentation
+    /// strategy got through - the signature of single-packet/single-record
+    /// SNI-matching DPI.
+    pub fn fragmentation_bypasses_dpi(&self) -> bool {
+        self.control != HandshakeOutcome::ServerHelloReceived && self.successful_strategies() > 0
+    }
+
+    /// Maps the comparison onto HAM's 0-10 scoring scale: the more
+    /// strategies succeed where the control failed, the higher the score.
+    pub fn score(&self) -> u8 {
+        if self.control == HandshakeOutcome::ServerHelloReceived {
+            return 7; // nothing to bypass here - site isn't SNI-filtered
+        }
+        match self.successful_strategies() {
+            0 => 1,
+            n if n == self.strategies.len() => 10,
+            _ => 7,
+        }
+    }
+}
+
+/// Runs the control handshake plus every [`SplitStrategy`] against `sni` on
+/// port 443.
+pub async fn probe_fragmentation(sni: &str) -> FragmentationProbeResult {
+    let control = attempt_handshake(sni, None).await;
+
+    let mut strategies = Vec::with_capacity(ALL_STRATEGIES.len());
+    for strategy in ALL_STRATEGIES {
+        let outcome = attempt_handshake(sni, Some(strategy)).await;
+        strategies.push(StrategyResult { strategy, outcome });
+    }
+
+    FragmentationProbeResult { control, strategies }
+}
+
+async fn attempt_handshake(sni: &str, strategy: Option<SplitStrategy>) -> HandshakeOutcome {
+    let addr = format!("{sni}:443");
+    let mut stream = match timeout(Duration::from_secs(5), TcpStream::connect(&addr)).await {
+        Ok(Ok(stream)) => stream,
+        Ok(Err(_)) => return HandshakeOutcome::Reset,
+        Err(_) => return HandshakeOutcome::TimedOut,
+    };
+    let _ = stream.set_nodelay(true);
+
+    let client_hello = ClientHello::build(sni);
+
+    let write_result = match strategy {
+        Some(SplitStrategy::TcpSegmentation) => write_tcp_segmented(&mut stream, &client_hello).await,
+        Some(SplitStrategy::TlsRecordSplit) => write_record_split(&mut stream, &client_hello).await,
+        None => stream.write_all(&client_hello.record).await,
+    };
+
+    if write_result.is_err() {
+        return HandshakeOutcome::Reset;
+    }
+
+    let mut buf = [0u8; 5];
+    match timeout(Duration::from_secs(5), stream.read_exact(&mut buf)).await {
+        Ok(Ok(_)) if buf[0] == 0x16 => HandshakeOutcome::ServerHelloReceived,
+        Ok(Ok(_)) => HandshakeOutcome::Reset,
+        Ok(Err(_)) => HandshakeOutcome::Reset,
+        Err(_) => HandshakeOutcome::TimedOut,
+    }
+}
+
+/// Splits the ClientHello at the TCP level so the `server_name` value
+/// straddles two segments: write everything up to the midpoint of the SNI
+/// value in one `write_all`, flush, pause briefly so the peer's reassembly
+/// sees two distinct segments, then write the remainder.
+async fn write_tcp_segmented(stream: &mut TcpStream, hello: &ClientHello) -> std::io::Result<()> {
+    let split_at = hello.sni_value_midpoint();
+    let (first, rest) = hello.record.split_at(split_at);
+
+    stream.write_all(first).await?;
+    stream.flush().await?;
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    stream.write_all(rest).await?;
+    stream.flush().await
+}
+
+/// Splits the ClientHello at the TLS record level: everything up to the
+/// midpoint of the `server_name` value becomes its own complete TLS record
+/// (with its own 5-byte header), and the remainder becomes a second record.
+/// Both are written back to back so TCP may well deliver them in a single
+/// segment, but a DPI box that only parses one handshake record per TLS
+/// record boundary won't see the full SNI in either.
+async fn write_record_split(stream: &mut TcpStream, hello: &ClientHello) -> std::io::Result<()> {
+    let handshake = &hello.record[5..]; // strip the original record header
+    let split_at = hello.sni_value_midpoint() - 5;
+    let (first, second) = handshake.split_at(split_at);
+
+    stream.write_all(&wrap_record(first)).await?;
+    stream.flush().await?;
+    stream.write_all(&wrap_record(second)).await?;
+    stream.flush().await
+}
+
+fn wrap_record(handshake_fragment: &[u8]) -> Vec<u8> {
+    let mut record = Vec::with_capacity(handshake_fragment.len() + 5);
+    record.push(0x16); // content type: handshake
+    record.extend_from_slice(&[0x03, 0x01]); // record version
+    record.extend_from_slice(&(handshake_fragment.len() as u16).to_be_bytes());
+    record.extend_from_slice(handshake_fragment);
+    record
+}
+
+/// A built ClientHello record plus the byte offset (within `record`) of the
+/// `server_name` value, so callers can split precisely through it rather
+/// than guessing at the midpoint of the whole message.
+struct ClientHello {
+    record: Vec<u8>,
+    sni_value_offset: usize,
+    sni_value_len: usize,
+}
+
+impl ClientHello {
+    /// Builds a minimal, syntactically valid TLS 1.2 ClientHello offering a
+    /// single common cipher suite and an SNI extension for `sni`. This is
+    /// intentionally bare-bones: its only job is to exercise DPI that
+    /// matches on the SNI extension, not to complete a production handshake
+    /// with arbitrary servers.
+    fn build(sni: &str) -> Self {
+        let mut body = Vec::new();
+        body.extend_from_slice(&[0x03, 0x03]); // client_version: TLS 1.2
+        body.extend_from_slice(&random_bytes(32)); // random
+        body.push(0x00); // session_id length
+
+        // cipher suites: TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256
+        body.extend_from_slice(&[0x00, 0x02, 0xc0, 0x2f]);
+        body.extend_from_slice(&[0x01, 0x00]); // compression methods: null
+
+        let (sni_ext, sni_value_offset_in_ext) = build_sni_extension(sni);
+        body.extend_from_slice(&(sni_ext.len() as u16).to_be_bytes());
+        let sni_ext_offset_in_body = body.len();
+        body.extend_from_slice(&sni_ext);
+
+        let mut handshake = Vec::new();
+        handshake.push(0x01); // handshake type: client_hello
+        let len = body.len() as u32;
+        handshake.extend_from_slice(&len.to_be_bytes()[1..]); // 24-bit length
+        let body_offset_in_handshake = handshake.len();
+        handshake.extend_from_slice(&body);
+
+        let mut record = Vec::new();
+        record.push(0x16); // content type: handshake
+        record.extend_from_slice(&[0x03, 0x01]); // record version
+        record.extend_from_slice(&(handshake.len() as u16).to_be_bytes());
+        let handshake_offset_in_record = record.len();
+        record.extend_from_slice(&handshake);
+
+        let sni_value_offset = handshake_offset_in_record
+            + body_offset_in_handshake
+            + sni_ext_offset_in_body
+            + sni_value_offset_in_ext;
+
+        ClientHello { record, sni_value_offset, sni_value_len: sni.len() }
+    }
+
+    /// Byte offset within `record` to split at so the `server_name` value is
+    /// cut roughly in half.
+    fn sni_value_midpoint(&self) -> usize {
+        self.sni_value_offset + (self.sni_value_len / 2).max(1)
+    }
+}
+
+/// Builds the `server_name` extension and returns it alongside the byte
+/// offset of the hostname value within the extension (type + length headers
+/// precede it).
+fn build_sni_extension(sni: &str) -> (Vec<u8>, usize) {
+    let mut server_name_list = Vec::new();
+    server_name_list.push(0x00); // name type: host_name
+    server_name_list.extend_from_slice(&(sni.len() as u16).to_be_bytes());
+    let sni_value_offset_in_list = server_name_list.len();
+    server_name_list.extend_from_slice(sni.as_bytes());
+
+    let mut ext_body = Vec::new();
+    ext_body.extend_from_slice(&(server_name_list.len() as u16).to_be_bytes());
+    let list_offset_in_ext_body = ext_body.len();
+    ext_body.extend_from_slice(&server_name_list);
+
+    let mut ext = Vec::new();
+    ext.extend_from_slice(&[0x00, 0x00]); // extension type: server_name
+    ext.extend_from_slice(&(ext_body.len() as u16).to_be_bytes());
+    let ext_body_offset_in_ext = ext.len();
+    ext.extend_from_slice(&ext_body);
+
+    let sni_value_offset_in_ext = ext_body_offset_in_ext + list_offset_in_ext_body + sni_value_offset_in_list;
+    (ext, sni_value_offset_in_ext)
+}
+
+fn random_bytes(n: usize) -> Vec<u8> {
+    // Only used to fill the ClientHello's `random` field for a connectivity
+    // probe the server will never successfully complete a session with, so
+    // time-based entropy is sufficient.
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    (0..n).map(|i| ((seed >> (i % 16)) ^ (i as u128 * 2654435761)) as u8).collect()
+}
@@ -0,0 +1,215 @@
+//! A real TTL-stepping traceroute used to localize *where* on the path
+//! blocking occurs, rather than just whether a destination is reachable.
+//!
+//! We send raw ICMP Echo Requests with increasing TTL (`1..=max_ttl`),
+//! collecting per-hop RTT from whatever comes back - an ICMP Time Exceeded
+//! from an intermediate router, or the final Echo Reply from the
+//! destination itself. Several probes are sent per hop to tolerate loss,
+//! replies are matched back to their hop by sequence number (so duplicate or
+//! reordered replies don't get attributed to the wrong hop), and hops that
+//! drop TTL-expired packets but still forward data further down the path
+//! just show up as silent without aborting the whole trace.
+
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+const ICMP_ECHO_REQUEST: u8 = 8;
+const ICMP_ECHO_REPLY: u8 = 0;
+const ICMP_TIME_EXCEEDED: u8 = 11;
+const PROBES_PER_HOP: u32 = 3;
+const PER_PROBE_TIMEOUT: Duration = Duration::from_millis(800);
+
+#[derive(Debug, Clone)]
+pub struct HopReport {
+    pub ttl: u32,
+    /// One RTT sample per probe that actually got a reply; shorter than
+    /// `PROBES_PER_HOP` when some probes were lost.
+    pub rtts: Vec<Duration>,
+    /// The address that answered this hop, if any did.
+    pub responder: Option<Ipv4Addr>,
+    pub reached_destination: bool,
+}
+
+impl HopReport {
+    pub fn responded(&self) -> bool {
+        !self.rtts.is_empty()
+    }
+}
+
+/// Runs the TTL-stepping trace against `target` (an IPv4 address), sending
+/// [`PROBES_PER_HOP`] probes per TTL and continuing until the destination
+/// replies or `silent_hop_run` consecutive hops produce no response at all
+/// (suggesting the path is filtered from that point on).
+pub async fn run_traceroute(target: Ipv4Addr, max_ttl: u32, silent_hop_run: u32) -> std::io::Result<Vec<HopReport>> {
+    // Raw ICMP sockets require CAP_NET_RAW/root, same as the system `ping`
+    // binary the rest of HAM already shells out to elsewhere.
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4))?;
+    socket.set_nonblocking(true)?;
+
+    let identifier = std::process::id() as u16;
+    let mut hops = Vec::new();
+    let mut consecutive_silent = 0u32;
+
+    for ttl in 1..=max_ttl {
+        socket.set_ttl(ttl)?;
+        let hop = probe_hop(&socket, target, identifier, ttl).await?;
+        let responded = hop.responded();
+        let reached = hop.reached_destination;
+        hops.push(hop);
+
+        if responded {
+            consecutive_silent = 0;
+        } else {
+            consecutive_silent += 1;
+        }
+
+        if reached || consecutive_silent >= silent_hop_run {
+            break;
+        }
+    }
+
+    Ok(hops)
+}
+
+async fn probe_hop(socket: &Socket, target: Ipv4Addr, identifier: u16, ttl: u32) -> std::io::Result<HopReport> {
+    let mut rtts = Vec::new();
+    let mut responder = None;
+    let mut reached_destination = false;
+
+    for probe_index in 0..PROBES_PER_HOP {
+        let sequence = (ttl * PROBES_PER_HOP + probe_index) as u16;
+        let packet = build_echo_request(identifier, sequence);
+        let dest = SockAddr::from(std::net::SocketAddrV4::new(target, 0));
+
+        let started = Instant::now();
+        if socket.send_to(&packet, &dest).is_err() {
+            continue;
+        }
+
+        match wait_for_reply(socket, identifier, sequence, target, PER_PROBE_TIMEOUT).await {
+            Some((from, is_destination)) => {
+                rtts.push(started.elapsed());
+                responder = Some(from);
+                reached_destination |= is_destination;
+            }
+            None => continue, // lost probe; tolerated, other probes at this hop may still land
+        }
+    }
+
+    Ok(HopReport { ttl, rtts, responder, reached_destination })
+}
+
+/// Polls the raw socket for a reply that matches our identifier/sequence,
+/// ignoring anything else that arrives (stray replies from unrelated
+/// traffic, or replies belonging to a different hop's probe).
+async fn wait_for_reply(
+    socket: &Socket,
+    identifier: u16,
+    sequence: u16,
+    target: Ipv4Addr,
+    timeout: Duration,
+) -> Option<(Ipv4Addr, bool)> {
+    let deadline = Instant::now() + timeout;
+    let mut buf = [std::mem::MaybeUninit::new(0u8); 1024];
+
+    while Instant::now() < deadline {
+        match socket.recv_from(&mut buf) {
+            Ok((len, from)) => {
+                let bytes: Vec<u8> = buf[..len].iter().map(|b| unsafe { b.assume_init() }).collect();
+                if let Some((responder, is_destination, matches)) = parse_reply(&bytes, identifier, sequence) {
+                    let from_ip = from
+                        .as_socket_ipv4()
+                        .map(|a| *a.ip())
+                        .unwrap_or(responder);
+                    if matches {
+                        return Some((from_ip, is_destination || from_ip == target));
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Err(_) => return None,
+        }
+    }
+    None
+}
+
+/// Parses an IPv4 + ICMP reply, returning `(responder, reached_destination,
+/// matches_our_probe)` when it's a reply we can attribute to our probe.
+fn parse_reply(bytes: &[u8], identifier: u16, sequence: u16) -> Option<(Ipv4Addr, bool, bool)> {
+    if bytes.len() < 20 {
+        return None;
+    }
+    let ip_header_len = ((bytes[0] & 0x0f) as usize) * 4;
+    if bytes.len() < ip_header_len + 8 {
+        return None;
+    }
+    let responder = Ipv4Addr::new(bytes[12], bytes[13], bytes[14], bytes[15]);
+    let icmp = &bytes[ip_header_len..];
+    let icmp_type = icmp[0];
+
+    match icmp_type {
+        ICMP_ECHO_REPLY => {
+            let reply_id = u16::from_be_bytes([icmp[4], icmp[5]]);
+            let reply_seq = u16::from_be_bytes([icmp[6], icmp[7]]);
+            let matches = reply_id == identifier && reply_seq == sequence;
+            Some((responder, true, matches))
+        }
+        ICMP_TIME_EXCEEDED => {
+            // The original IP header + first 8 bytes of our echo request are
+            // embedded after the Time Exceeded header (8 bytes), letting us
+            // confirm this exceeded packet really was ours.
+            let embedded = icmp.get(8..)?;
+            let embedded_ip_header_len = ((embedded.first()? & 0x0f) as usize) * 4;
+            let embedded_icmp = embedded.get(embedded_ip_header_len..embedded_ip_header_len + 8)?;
+            let original_id = u16::from_be_bytes([embedded_icmp[4], embedded_icmp[5]]);
+            let original_seq = u16::from_be_bytes([embedded_icmp[6], embedded_icmp[7]]);
+            let matches = original_id == identifier && original_seq == sequence;
+            Some((responder, false, matches))
+        }
+        _ => None,
+    }
+}
+
+fn build_echo_request(identifier: u16, sequence: u16) -> Vec<u8> {
+    let mut packet = vec![0u8; 16];
+    packet[0] = ICMP_ECHO_REQUEST;
+    packet[1] = 0; // code
+    packet[4..6].copy_from_slice(&identifier.to_be_bytes());
+    packet[6..8].copy_from_slice(&sequence.to_be_bytes());
+    // Payload: a small timestamp-free marker, enough to exercise a normal
+    // echo request without needing a monotonic payload.
+    packet[8..16].copy_from_slice(b"hamtrace");
+
+    let checksum = icmp_checksum(&packet);
+    packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+    packet
+}
+
+fn icmp_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Summarizes a full trace into the boolean per-hop pattern HAM's Iran
+/// analysis already expects (whether each hop responded at all), plus the
+/// hop index where responses stopped, for callers that don't need the full
+/// RTT detail.
+pub fn summarize(hops: &[HopReport]) -> (Vec<bool>, Option<u32>) {
+    let pattern: Vec<bool> = hops.iter().map(|h| h.responded()).collect();
+    let blocking_hop = hops.iter().find(|h| !h.responded()).map(|h| h.ttl);
+    (pattern, blocking_hop)
+}